@@ -20,6 +20,7 @@ fn main() -> Result<(), MassMoveError> {
         source_pattern: format!("{}/some_*_filename.txt", dir.path().display()),
         destination_pattern: format!("{}/changed_#1_filename.txt", dir.path().display()),
         force: true,
+        ..Default::default()
     };
 
     mmv(args)?;
@@ -7,6 +7,7 @@ fn main() {
         source_pattern: "non_existent_files/*.txt".to_string(),
         destination_pattern: "new_location/new_file_#1.txt".to_string(),
         force: false,
+        ..Default::default()
     };
 
     let result = mmv(args);
@@ -1,11 +1,42 @@
 pub extern crate clap;
-use clap::Parser;
+use crate::conflict::ConflictPolicy;
+use crate::fs_utils::Operation;
+use crate::interactive::InteractiveMode;
+use crate::links::SymlinkPolicy;
+use crate::open_files::OpenFilePolicy;
+use crate::special::SpecialPolicy;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 /// Struct to parse CLI arguments.
 /// # Arguments
 /// - `source_pattern` - the pattern by which the search will take place. It should contain only * and only in filename. Example: 'path/to/some_*_filename.*'
 /// - `destination_pattern` - the pattern by which files will be renamed. It sshould contains markers only in filename. Example: 'path2/to/changed_#1_filename.#2'
 /// - `force` - flag of CLI app, that overwrites existing files if they exist.
+/// - `interactive` - flag of CLI app, that asks for a confirmation before every rename, or with `=overwrite` only before overwriting an existing file.
+/// - `mode` - how files are transferred: `move`, `copy`, `hardlink`, `symlink`, `reflink`, `append` or `exchange`.
+/// - `append` - flag of CLI app, that appends sources to their destinations, same as `--mode append`.
+/// - `exchange` - flag of CLI app, that atomically swaps sources with existing destinations, same as `--mode exchange`.
+/// - `on_conflict` - what to do if a destination file already exists: `error`, `skip`, `overwrite`, `rename`, `newer`, `larger`, `backup` or `ask`.
+/// - `backup_dir` - directory where destination files are kept, under their relative path and with a timestamp suffix, before they are overwritten.
+/// - `trash` - flag of CLI app, that moves destination files into the trash of the user instead of overwriting them.
+/// - `progress` - flag of CLI app, that shows the progress of copies to another file system.
+/// - `verify` - flag of CLI app, that compares the hashes of copies to another file system with their sources before deleting the sources.
+/// - `symlinks` - option of CLI app, that decides whether symbolic links are moved themselves, replaced by the files they point to, or skipped.
+/// - `rewrite_links` - flag of CLI app, that rewrites relative targets of moved symbolic links so they still resolve from the new location.
+/// - `special` - option of CLI app, that decides whether named pipes, sockets and device nodes are skipped with a warning or recreated at the destination.
+/// - `fix_links` - option of CLI app, that retargets symbolic links under the given directory which point at moved files.
+/// - `update_refs` - option of CLI app, that rewrites references to moved files in the text files matching the given glob pattern.
+/// - `dry_run` - flag of CLI app, that only prints the planned moves and the reference changes as a diff.
+/// - `journal` - flag of CLI app, that records every moved file in the journal of the batch, not only copies to another file system.
+/// - `resume` - flag of CLI app, that finishes a batch interrupted before, skipping the files its journal records as done.
+/// - `jobs` - number of files moved concurrently, after files depending on each other are grouped.
+/// - `keep_going` - flag of CLI app, that attempts every file even if some of them fail and reports all failures at the end.
+/// - `skip_open` - option of CLI app, that skips files open in another process with a warning, or with `=fail` moves no file if any of them is open.
+/// - `lock` - option of CLI app, that takes an exclusive `flock` on the given lock file, or on `.mmv-lock` in the common parent directory of the patterns, for the whole batch.
+/// - `wait` - how many seconds to wait for another run to release the lock, instead of failing at once.
+/// - `tag_origin` - flag of CLI app, that records the original path of every moved file in its `user.mmv.origin` extended attribute.
+/// - `command` - an optional subcommand used instead of the patterns, e.g. `mmv restore PATTERN`.
 /// # Example
 /// ```rust
 /// use mmv::args::CLI;
@@ -24,27 +55,172 @@ use clap::Parser;
 ///     source_pattern: format!("{}/some_*_filename.txt", dir.path().display()),
 ///     destination_pattern: format!("{}/changed_#1_filename.txt", dir.path().display()),
 ///     force: false,
+///     ..Default::default()
 ///     };
 /// }
 /// ```
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[command(
     author = "Victoria Kashurkina",
     name = "mmv",
     version = "1.0",
     about = "This is a CLI tool to move and rename
- acll files matched by pattern."
+ acll files matched by pattern.",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 pub struct CLI {
+    // The empty defaults are only used by subcommands, which do not take the patterns.
     /// Pattern to search for files in the directory.
-    #[arg(long)]
+    #[arg(long, required = true, default_value = "", hide_default_value = true)]
     pub source_pattern: String,
 
     /// Pattern to rename files.
-    #[arg(long)]
+    #[arg(long, required = true, default_value = "", hide_default_value = true)]
     pub destination_pattern: String,
 
     /// Overwrite existing files
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with = "on_conflict")]
     pub force: bool,
+
+    /// How files are transferred to their destinations
+    #[arg(long, value_enum, default_value_t = Operation::Move)]
+    pub mode: Operation,
+
+    /// Append sources to their destinations, same as `--mode append`
+    #[arg(short, long, conflicts_with = "mode")]
+    pub append: bool,
+
+    /// Atomically swap sources with existing destinations, same as `--mode exchange`
+    #[arg(long, conflicts_with_all = ["mode", "append"])]
+    pub exchange: bool,
+
+    /// What to do if the destination file already exists
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Error)]
+    pub on_conflict: ConflictPolicy,
+
+    /// Keep overwritten destination files in this directory
+    #[arg(long, value_name = "DIR")]
+    pub backup_dir: Option<PathBuf>,
+
+    /// Move files that would be overwritten into the trash, so they can be restored by file managers
+    #[arg(long, conflicts_with = "backup_dir")]
+    pub trash: bool,
+
+    /// Show the progress of copies to another file system
+    #[arg(long)]
+    pub progress: bool,
+
+    /// Verify copies to another file system by their hash before deleting the sources
+    #[arg(long)]
+    pub verify: bool,
+
+    /// What to do with source files that are symbolic links
+    #[arg(long, value_enum, default_value_t = SymlinkPolicy::Preserve)]
+    pub symlinks: SymlinkPolicy,
+
+    /// Rewrite relative targets of moved symbolic links so they still point at the same files
+    #[arg(long)]
+    pub rewrite_links: bool,
+
+    /// What to do with source files that are named pipes, sockets or device nodes
+    #[arg(long, value_enum, default_value_t = SpecialPolicy::Skip)]
+    pub special: SpecialPolicy,
+
+    /// Retarget symbolic links under ROOT that point at moved files
+    #[arg(long, value_name = "ROOT")]
+    pub fix_links: Option<PathBuf>,
+
+    /// Rewrite references to moved files in the text files matching GLOB
+    #[arg(long, value_name = "GLOB")]
+    pub update_refs: Option<String>,
+
+    /// Only print the planned moves and the reference changes as a diff, without changing any file
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Record every moved file in the journal of the batch, not only copies to another file system
+    #[arg(long)]
+    pub journal: bool,
+
+    /// Finish a batch that was interrupted before, skipping the files it already moved
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Move up to N independent files concurrently
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        conflicts_with = "interactive"
+    )]
+    pub jobs: usize,
+
+    /// Continue with the remaining files after a failure and report all failures at the end
+    #[arg(short, long)]
+    pub keep_going: bool,
+
+    /// Ask before every rename, or with `=overwrite` only if the destination exists
+    #[arg(
+        short,
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "always"
+    )]
+    pub interactive: Option<InteractiveMode>,
+
+    /// Skip files that are open in another process with a warning, or with `=fail` move no file if any of them is open
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "skip"
+    )]
+    pub skip_open: Option<OpenFilePolicy>,
+
+    /// Take an exclusive lock on PATH, by default `.mmv-lock` in the common parent directory of the patterns
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    pub lock: Option<Option<PathBuf>>,
+
+    /// Wait up to SECONDS for another run to release the lock instead of failing at once
+    #[arg(long, value_name = "SECONDS", requires = "lock")]
+    pub wait: Option<u64>,
+
+    /// Record the original path of every moved file in its `user.mmv.origin` extended attribute
+    #[arg(long)]
+    pub tag_origin: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands of the CLI app, used instead of `--source-pattern` and `--destination-pattern`.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Move files tagged by `--tag-origin` back to their original paths
+    Restore {
+        /// Pattern of the moved files to restore.
+        pattern: String,
+
+        /// Overwrite existing files at the original paths
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+impl CLI {
+    /// Returns the operation selected by `--mode` or by one of its shorthands `--append` and `--exchange`.
+    pub fn operation(&self) -> Operation {
+        if self.append {
+            Operation::Append
+        } else if self.exchange {
+            Operation::Exchange
+        } else {
+            self.mode
+        }
+    }
 }
@@ -0,0 +1,301 @@
+use crate::errors::MassMoveError;
+use crate::interactive::{Decision, Interactive, InteractiveMode};
+use crate::links::{absolute, normalize};
+use clap::ValueEnum;
+use std::{
+    ffi::OsString,
+    fmt, fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// What to do if the destination file already exists.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Refuse to move the file.
+    #[default]
+    Error,
+    /// Leave the source file in place.
+    Skip,
+    /// Replace the destination file.
+    Overwrite,
+    /// Move the file to a free name with a `_N` suffix before the extension.
+    Rename,
+    /// Replace the destination file only if the source file was modified later.
+    Newer,
+    /// Replace the destination file only if the source file is larger.
+    Larger,
+    /// Keep the destination file as `name~`, or as `name.~N~` if `name~` is taken.
+    Backup,
+    /// Ask the user what to do.
+    Ask,
+}
+
+/// The way a conflict with an existing destination file is resolved.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Leave the source file in place.
+    Skip,
+    /// Replace the destination file.
+    Overwrite,
+    /// Move the source file to another path instead.
+    Rename(PathBuf),
+    /// Move the destination file to the backup path, then replace it.
+    Backup(PathBuf),
+    /// Leave this and all remaining files in place.
+    Quit,
+}
+
+/// The prompt of `ConflictPolicy::Ask`. It is shared by all files of a batch, so after `[a]ll` the following conflicts
+/// are resolved by overwriting without asking again.
+/// # Example
+/// ```rust
+/// use mmv::conflict::{ConflictPrompt, Resolution};
+/// use mmv::errors::MassMoveError;
+/// use std::fs::File;
+/// use std::io::{self, Cursor};
+/// use tempfile::TempDir;
+///
+/// fn main() -> Result<(), MassMoveError> {
+///     let dir = TempDir::new()?;
+///     let source = dir.path().join("source.txt");
+///     let destination = dir.path().join("report.txt");
+///     File::create(&source)?;
+///     File::create(&destination)?;
+///
+///     let prompt = ConflictPrompt::new(Cursor::new("a\n"), io::sink());
+///     assert_eq!(prompt.resolve(&source, &destination)?, Resolution::Overwrite);
+///     assert_eq!(prompt.resolve(&source, &destination)?, Resolution::Overwrite);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ConflictPrompt(Arc<Mutex<BoxedInteractive>>);
+
+type BoxedInteractive = Interactive<Box<dyn BufRead + Send>, Box<dyn Write + Send>>;
+
+impl ConflictPrompt {
+    /// Creates a prompt reading answers from `input` and writing questions to `output`.
+    pub fn new(input: impl BufRead + Send + 'static, output: impl Write + Send + 'static) -> Self {
+        ConflictPrompt(Arc::new(Mutex::new(Interactive::new(
+            InteractiveMode::Overwrite,
+            Box::new(input),
+            Box::new(output),
+        ))))
+    }
+
+    /// Creates a prompt reading answers from stdin and writing questions to stdout.
+    pub fn stdio() -> Self {
+        ConflictPrompt::new(BufReader::new(io::stdin()), io::stdout())
+    }
+
+    /// This function asks the user what to do with `source` because `destination` already exists.
+    /// # Return value
+    /// Returns `Result<Resolution, MassMoveError>`, where:
+    /// - If succesful - returns `Resolution::Overwrite` for `[y]es` and `[a]ll`, `Resolution::Rename` for an edited name,
+    ///   `Resolution::Skip` for `[n]o` and `Resolution::Quit` for `[q]uit` or the end of the input.
+    /// - In case of an error, a `MassMoveError::StdIOError` occurs if the prompt could not be read or written.
+    pub fn resolve(&self, source: &Path, destination: &Path) -> Result<Resolution, MassMoveError> {
+        let source = source.display().to_string();
+        let destination = destination.display().to_string();
+        match self.0.lock().unwrap().confirm(&source, &destination)? {
+            Decision::Move {
+                destination: edited,
+                ..
+            } if edited == destination => Ok(Resolution::Overwrite),
+            Decision::Move {
+                destination: edited,
+                ..
+            } => Ok(Resolution::Rename(PathBuf::from(edited))),
+            Decision::Skip => Ok(Resolution::Skip),
+            Decision::Quit => Ok(Resolution::Quit),
+        }
+    }
+}
+
+impl fmt::Debug for ConflictPrompt {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("ConflictPrompt")
+    }
+}
+
+impl ConflictPolicy {
+    /// This function decides what to do with `source` if `destination` already exists.
+    /// # Return value
+    /// Returns `Result<Resolution, MassMoveError>`, where:
+    /// - If succesful - returns the resolution of the conflict.
+    /// - In case of an error, a `MassMoveError` type error occurs if the policy refuses to move the file or the files could not be inspected.
+    /// # Example
+    /// ```rust
+    /// use mmv::conflict::{ConflictPolicy, Resolution};
+    /// use mmv::errors::MassMoveError;
+    /// use std::fs::File;
+    /// use tempfile::TempDir;
+    ///
+    /// fn main() -> Result<(), MassMoveError> {
+    ///     let dir = TempDir::new()?;
+    ///     let source = dir.path().join("source.txt");
+    ///     let destination = dir.path().join("report.txt");
+    ///     File::create(&source)?;
+    ///     File::create(&destination)?;
+    ///
+    ///     let resolution = ConflictPolicy::Rename.resolve(&source, &destination)?;
+    ///     assert_eq!(resolution, Resolution::Rename(dir.path().join("report_1.txt")));
+    ///     Ok(())
+    /// }
+    /// ```
+    /// # Possible errors
+    /// - `MassMoveError::FileExistsError` - if the policy is `ConflictPolicy::Error`.
+    /// - `MassMoveError::StdIOError` - if metadata of the files could not be read or the user could not be asked.
+    /// # Behavior
+    /// `ConflictPolicy::Ask` asks on stdin with a new prompt, so `[a]ll` only applies to this conflict. Batches share one
+    /// `ConflictPrompt` instead, see `MoveOptions::prompt`.
+    pub fn resolve(&self, source: &Path, destination: &Path) -> Result<Resolution, MassMoveError> {
+        match self {
+            ConflictPolicy::Error => Err(MassMoveError::FileExistsError(format!(
+                "The file {} already exists. Try --force mode to overwrite the file",
+                destination.display()
+            ))),
+            ConflictPolicy::Skip => Ok(Resolution::Skip),
+            ConflictPolicy::Overwrite => Ok(Resolution::Overwrite),
+            ConflictPolicy::Rename => Ok(Resolution::Rename(free_suffixed_path(destination))),
+            ConflictPolicy::Newer => {
+                let source_modified = fs::metadata(source)?.modified()?;
+                let destination_modified = fs::metadata(destination)?.modified()?;
+                Ok(overwrite_if(source_modified > destination_modified))
+            }
+            ConflictPolicy::Larger => {
+                let source_len = fs::metadata(source)?.len();
+                let destination_len = fs::metadata(destination)?.len();
+                Ok(overwrite_if(source_len > destination_len))
+            }
+            ConflictPolicy::Backup => Ok(Resolution::Backup(backup_path(destination))),
+            ConflictPolicy::Ask => ConflictPrompt::stdio().resolve(source, destination),
+        }
+    }
+}
+
+fn overwrite_if(condition: bool) -> Resolution {
+    if condition {
+        Resolution::Overwrite
+    } else {
+        Resolution::Skip
+    }
+}
+
+/// Returns the first path of the form `name_N.ext` that does not exist yet.
+fn free_suffixed_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|ext| ext.to_string_lossy());
+    (1..)
+        .map(|n| {
+            let name = match &extension {
+                Some(ext) => format!("{}_{}.{}", stem, n, ext),
+                None => format!("{}_{}", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Returns `name~` if it does not exist yet, otherwise the first free `name.~N~`.
+fn backup_path(path: &Path) -> PathBuf {
+    let simple = with_name_suffix(path, "~");
+    if !simple.exists() {
+        return simple;
+    }
+    (1..)
+        .map(|n| with_name_suffix(path, &format!(".~{}~", n)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+fn with_name_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name: OsString = path.file_name().unwrap_or_default().to_owned();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// This function computes where an overwritten file is kept inside a backup directory.
+/// # Arguments
+/// - `backup_dir` - the directory given by `--backup-dir`.
+/// - `path` - the file that is about to be overwritten.
+/// # Return value
+/// Returns the path of `path` inside `backup_dir`, keeping its relative path (an absolute path is kept without its root)
+/// and adding a UTC timestamp suffix, e.g. `backups/dir/report.txt.20240101T120000Z`. If the same file was already backed
+/// up during the same second, a `.N` counter is added. `.` and `..` components are resolved first, and a relative path
+/// leading out of the current directory, e.g. `../dir/report.txt`, is kept as its absolute path.
+/// # Example
+/// ```rust
+/// use mmv::conflict::backup_dir_path;
+/// use std::path::Path;
+///
+/// let backup_file = backup_dir_path(Path::new("backups"), Path::new("dir/report.txt"));
+/// assert!(backup_file.starts_with("backups/dir"));
+/// assert!(backup_file.file_name().unwrap().to_string_lossy().starts_with("report.txt."));
+///
+/// let outside_file = backup_dir_path(Path::new("backups"), Path::new("../dir/report.txt"));
+/// assert!(!outside_file.starts_with("backups/dir"));
+/// ```
+pub fn backup_dir_path(backup_dir: &Path, path: &Path) -> PathBuf {
+    let mut path = normalize(path);
+    if path.starts_with("..") {
+        path = normalize(&absolute(&path));
+    }
+    let relative_path: PathBuf = path
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    let timestamped = with_name_suffix(
+        &backup_dir.join(relative_path),
+        &format!(".{}", timestamp(SystemTime::now())),
+    );
+    if !timestamped.exists() {
+        return timestamped;
+    }
+    (1..)
+        .map(|n| with_name_suffix(&timestamped, &format!(".{}", n)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Formats `time` as a compact UTC timestamp, e.g. `20240101T120000Z`.
+pub fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let seconds_of_day = seconds % 86400;
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Converts days since the Unix epoch into a `(year, month, day)` date of the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
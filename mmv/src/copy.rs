@@ -0,0 +1,406 @@
+use crate::errors::MassMoveError;
+use crate::sys;
+use std::{
+    fmt,
+    fs::{self, File, Metadata, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+};
+
+/// Size of the chunks in which `copy_file` copies the content and reports the progress.
+const CHUNK_SIZE: usize = 1 << 20;
+
+type ProgressCallback = dyn Fn(&Path, u64, u64) + Send + Sync;
+
+/// Callback receiving the source file, the number of bytes copied so far and the size of the file after every copied chunk.
+#[derive(Clone)]
+pub struct Progress(Arc<ProgressCallback>);
+
+impl Progress {
+    /// Wraps `callback` into a progress callback.
+    pub fn new(callback: impl Fn(&Path, u64, u64) + Send + Sync + 'static) -> Self {
+        Progress(Arc::new(callback))
+    }
+
+    /// Reports that `copied` of `total` bytes of `source` are copied.
+    pub fn report(&self, source: &Path, copied: u64, total: u64) {
+        (self.0)(source, copied, total)
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Progress")
+    }
+}
+
+/// How `copy_file` copies the content of a file. Every strategy falls back to the following ones if the file systems
+/// do not support it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CopyStrategy {
+    /// Share the extents of the source with a copy-on-write clone (`FICLONE`), e.g. on btrfs or XFS.
+    #[default]
+    Reflink,
+    /// Copy the data inside the kernel with `copy_file_range`, keeping the holes of sparse files.
+    CopyFileRange,
+    /// Read and write the data, keeping the holes of sparse files.
+    ReadWrite,
+}
+
+impl fmt::Display for CopyStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CopyStrategy::Reflink => "reflink",
+            CopyStrategy::CopyFileRange => "copy_file_range",
+            CopyStrategy::ReadWrite => "read/write",
+        })
+    }
+}
+
+/// What `copy_file` has done.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyReport {
+    /// The strategy that copied the content.
+    pub strategy: CopyStrategy,
+    /// The names of the attributes that could not be preserved, e.g. `owner` or `xattr user.comment`.
+    pub unpreserved: Vec<String>,
+}
+
+/// Options of `copy_file`.
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+    /// Whether an existing destination may be replaced. Otherwise the destination is created exclusively (`O_EXCL`).
+    pub overwrite: bool,
+    /// Callback called after every copied chunk of 1 MiB.
+    pub progress: Option<Progress>,
+    /// Whether to re-read the destination and compare its hash with the hash of the source.
+    pub verify: bool,
+    /// The first strategy to try.
+    pub strategy: CopyStrategy,
+}
+
+/// This function copies a file to another file system together with all metadata the process is allowed to set.
+/// # Arguments
+/// - `source` - the file to copy.
+/// - `destination` - the new file.
+/// - `options` - whether `destination` may be replaced, where to report the progress, whether to verify the copy
+///   and which strategy to try first.
+/// # Return value
+/// Returns `Result<CopyReport, MassMoveError>`, where:
+/// - If succesful - returns the strategy that copied the content and the names of the attributes that could not be preserved.
+///   An empty list of attributes means that the copy is indistinguishable from the source.
+/// - In case of an error, a `MassMoveError::FileExistsError` occurs if `destination` exists and `overwrite` is not set,
+///   a `MassMoveError::VerificationError` if `verify` is set and the content of the copy differs from the source,
+///   or a `MassMoveError::StdIOError` if the content could not be copied.
+/// # Example
+/// ```rust
+/// use mmv::copy::{copy_file, CopyOptions};
+/// use mmv::errors::MassMoveError;
+/// use std::fs;
+/// use tempfile::TempDir;
+///
+/// fn main() -> Result<(), MassMoveError> {
+///     let dir = TempDir::new()?;
+///     let source = dir.path().join("source.txt");
+///     let destination = dir.path().join("destination.txt");
+///     fs::write(&source, "content")?;
+///
+///     let options = CopyOptions { verify: true, ..Default::default() };
+///     let report = copy_file(&source, &destination, &options)?;
+///     assert!(report.unpreserved.is_empty());
+///     assert_eq!(
+///         fs::metadata(&source)?.modified()?,
+///         fs::metadata(&destination)?.modified()?
+///     );
+///     Ok(())
+/// }
+/// ```
+/// # Behavior
+/// - The content is cloned with `FICLONE` if the file system supports reflinks, otherwise copied inside the kernel with
+///   `copy_file_range`, otherwise read and written by the process. The last two strategies only copy the data ranges
+///   of the source (`SEEK_DATA`/`SEEK_HOLE`), so the holes of sparse files such as VM images are kept.
+/// - Before returning, the destination file and its directory are flushed to the disk with `fsync`, so the source can be
+///   deleted afterwards without risking to lose both copies on a power loss. If copying fails, the destination is removed.
+/// - With `verify`, the flushed destination is evicted from the page cache, read again from the disk and its hash is
+///   compared with the hash of the source. On a mismatch both files are left in place.
+/// - If the source gets shorter while it is copied, copying fails and the destination is removed.
+/// - If the process is interrupted by Ctrl-C (see `sys::catch_interrupts`), copying stops after the current chunk and the
+///   destination is removed.
+pub fn copy_file(
+    source: &Path,
+    destination: &Path,
+    options: &CopyOptions,
+) -> Result<CopyReport, MassMoveError> {
+    // Read the metadata first: copying the content updates the access time of the source.
+    let metadata = fs::metadata(source)?;
+    let source_file = fs::File::open(source)?;
+    let mut destination_file = match OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!options.overwrite)
+        .open(destination)
+    {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(MassMoveError::FileExistsError(format!(
+                "The file {} already exists.",
+                destination.display()
+            )))
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let copied = copy_content(
+        &source_file,
+        &mut destination_file,
+        source,
+        metadata.len(),
+        options,
+    )
+    .and_then(|strategy| {
+        destination_file.sync_all()?;
+        Ok(strategy)
+    });
+    let strategy = match copied {
+        Ok(strategy) => strategy,
+        Err(error) => {
+            drop(destination_file);
+            let _ = fs::remove_file(destination);
+            return Err(error.into());
+        }
+    };
+
+    // Verify before restoring the timestamps, reading the copy updates its access time.
+    if options.verify && !same_content(source, destination)? {
+        return Err(MassMoveError::VerificationError {
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+        });
+    }
+
+    let unpreserved = preserve_metadata(&metadata, source, destination);
+    let synced = destination_file
+        .sync_all()
+        .and_then(|_| sync_parent(destination));
+    if let Err(error) = synced {
+        drop(destination_file);
+        let _ = fs::remove_file(destination);
+        return Err(error.into());
+    }
+    Ok(CopyReport {
+        strategy,
+        unpreserved,
+    })
+}
+
+/// Copies the content with the first strategy the file systems support, starting with `options.strategy`.
+fn copy_content(
+    source_file: &File,
+    destination_file: &mut File,
+    source: &Path,
+    total: u64,
+    options: &CopyOptions,
+) -> io::Result<CopyStrategy> {
+    let progress = options.progress.as_ref();
+    if options.strategy == CopyStrategy::Reflink
+        && sys::clone_file(source_file, destination_file).is_ok()
+    {
+        if let Some(progress) = progress {
+            progress.report(source, total, total);
+        }
+        return Ok(CopyStrategy::Reflink);
+    }
+
+    let ranges = sys::data_ranges(source_file, total)?;
+    if options.strategy != CopyStrategy::ReadWrite {
+        let copied = copy_ranges(&ranges, source, total, progress, |offset, length| {
+            sys::copy_file_range(source_file, destination_file, offset, length)
+        });
+        match copied {
+            Ok(()) => return finish_ranges(destination_file, CopyStrategy::CopyFileRange, total),
+            // E.g. `EXDEV` on kernels that cannot copy between these file systems, `ENOSYS` or `EOPNOTSUPP`.
+            // Some file systems, e.g. procfs, and older kernels report a short copy instead, which read/write tells
+            // apart from a source that really got shorter.
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::CrossesDevices
+                        | io::ErrorKind::Unsupported
+                        | io::ErrorKind::InvalidInput
+                        | io::ErrorKind::UnexpectedEof
+                ) => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    let mut source_file = source_file;
+    let mut buffer = vec![0; CHUNK_SIZE];
+    copy_ranges(&ranges, source, total, progress, |offset, length| {
+        source_file.seek(SeekFrom::Start(offset))?;
+        let read = source_file.read(&mut buffer[..length])?;
+        destination_file.seek(SeekFrom::Start(offset))?;
+        destination_file.write_all(&buffer[..read])?;
+        Ok(read)
+    })?;
+    finish_ranges(destination_file, CopyStrategy::ReadWrite, total)
+}
+
+/// Extends the destination to the size of the source, so a hole at the end of the source is kept.
+fn finish_ranges(
+    destination_file: &File,
+    strategy: CopyStrategy,
+    total: u64,
+) -> io::Result<CopyStrategy> {
+    destination_file.set_len(total)?;
+    Ok(strategy)
+}
+
+/// Copies the data `ranges` in chunks of `CHUNK_SIZE` with `copy`, which copies up to the given number of bytes
+/// at the given offset and returns how many it copied. Returns an error of kind `io::ErrorKind::UnexpectedEof` if
+/// `copy` copies nothing before the end of a range, so the copy is never padded with zeros.
+fn copy_ranges(
+    ranges: &[(u64, u64)],
+    source: &Path,
+    total: u64,
+    progress: Option<&Progress>,
+    mut copy: impl FnMut(u64, usize) -> io::Result<usize>,
+) -> io::Result<()> {
+    let mut reported = 0;
+    for &(start, end) in ranges {
+        let mut offset = start;
+        while offset < end {
+            if sys::interrupted() {
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "The copy was interrupted by the user.",
+                ));
+            }
+            let chunk_end = end.min(offset + CHUNK_SIZE as u64);
+            while offset < chunk_end {
+                match copy(offset, (chunk_end - offset) as usize) {
+                    Ok(0) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            format!(
+                                "The file {} got shorter while it was copied.",
+                                source.display()
+                            ),
+                        ))
+                    }
+                    Ok(copied) => offset += copied as u64,
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(error) => return Err(error),
+                }
+            }
+            if let Some(progress) = progress {
+                progress.report(source, offset, total);
+            }
+            reported = offset;
+        }
+    }
+    if let Some(progress) = progress.filter(|_| reported < total) {
+        progress.report(source, total, total);
+    }
+    Ok(())
+}
+
+/// This function checks whether `copy` has the same content as `original`, comparing them byte by byte.
+/// # Return value
+/// Returns `io::Result<bool>`, where:
+/// - If succesful - returns `true` if both files have the same content.
+/// - In case of an error, the OS error is returned if one of the files could not be read.
+/// # Behavior
+/// The copy is read from the disk, not from the page cache that may still hold the data written to it. This is best
+/// effort: if the cache cannot be dropped, the copy is still compared.
+pub fn same_content(original: &Path, copy: &Path) -> io::Result<bool> {
+    let (mut original, mut copy) = (File::open(original)?, File::open(copy)?);
+    let _ = sys::drop_cache(&copy);
+    let (mut left, mut right) = (vec![0; CHUNK_SIZE], vec![0; CHUNK_SIZE]);
+    loop {
+        let read = match original.read(&mut left) {
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        if read == 0 {
+            return Ok(copy.read(&mut right[..1])? == 0);
+        }
+        match copy.read_exact(&mut right[..read]) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(error) => return Err(error),
+        }
+        if left[..read] != right[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// This function flushes the directory entry of `path` to the disk by calling `fsync` on its parent directory.
+pub fn sync_parent(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// This function copies ownership, extended attributes (including POSIX ACLs), permissions and timestamps
+/// described by `metadata` of `source` onto `destination`.
+/// # Return value
+/// Returns the names of the attributes that could not be set, e.g. `owner` if the process is not allowed to `chown`.
+pub fn preserve_metadata(metadata: &Metadata, source: &Path, destination: &Path) -> Vec<String> {
+    let mut unpreserved = Vec::new();
+
+    // Ownership first: `chown` may clear the set-user-ID and set-group-ID bits.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{chown, MetadataExt};
+
+        if chown(destination, Some(metadata.uid()), Some(metadata.gid())).is_err() {
+            unpreserved.push("owner".to_string());
+            if chown(destination, None, Some(metadata.gid())).is_err() {
+                unpreserved.push("group".to_string());
+            }
+        }
+    }
+
+    match sys::list_xattrs(source) {
+        Ok(names) => {
+            for name in names {
+                let copied = sys::get_xattr(source, &name)
+                    .and_then(|value| sys::set_xattr(destination, &name, &value));
+                if copied.is_err() {
+                    unpreserved.push(format!("xattr {}", name.to_string_lossy()));
+                }
+            }
+        }
+        Err(error) if error.kind() == io::ErrorKind::Unsupported => {}
+        Err(_) => unpreserved.push("xattrs".to_string()),
+    }
+
+    if fs::set_permissions(destination, metadata.permissions()).is_err() {
+        unpreserved.push("permissions".to_string());
+    }
+
+    // Timestamps last, every other change only touches the change time.
+    let times = metadata
+        .accessed()
+        .and_then(|accessed| Ok((accessed, metadata.modified()?)));
+    if times
+        .and_then(|(accessed, modified)| sys::set_times(destination, accessed, modified))
+        .is_err()
+    {
+        unpreserved.push("timestamps".to_string());
+    }
+
+    unpreserved
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 /// Enumuration of errors fo Mass Mover Project.
 #[derive(Debug)]
 pub enum MassMoveError {
@@ -15,6 +17,64 @@ pub enum MassMoveError {
     RegexError(regex::Error),
     /// Error, if there are no matches between a source pattern and a path.
     MatchError(String),
+    /// Error, if the process is not allowed to rename `source` to `destination`.
+    PermissionDeniedError {
+        source: PathBuf,
+        destination: PathBuf,
+    },
+    /// Error, if `source` disappeared before it could be renamed to `destination`.
+    SourceNotFoundError {
+        source: PathBuf,
+        destination: PathBuf,
+    },
+    /// Error, if the directory of `destination` does not exist.
+    MissingDirectoryError {
+        source: PathBuf,
+        destination: PathBuf,
+    },
+    /// Any other error of renaming `source` to `destination`, e.g. if the destination is a directory or the file system is read-only.
+    RenameError {
+        source: PathBuf,
+        destination: PathBuf,
+        error: std::io::Error,
+    },
+    /// Error, if the file system, the kernel or the platform cannot atomically exchange `source` and `destination`.
+    ExchangeUnsupportedError {
+        source: PathBuf,
+        destination: PathBuf,
+    },
+    /// Error, if the copy of `source` at `destination` differs from the source. Both files are left in place.
+    VerificationError {
+        source: PathBuf,
+        destination: PathBuf,
+    },
+    /// Error, if `path` is open in other `processes` and `--skip-open=fail` refuses to move the batch.
+    FileInUseError {
+        path: PathBuf,
+        processes: Vec<String>,
+    },
+    /// Error, if the text file at `path` was changed after its reference updates were computed and is left as it is.
+    FileChangedError { path: PathBuf },
+    /// Error, if another run holds the lock file at `path` and `--wait` expired or was not given.
+    LockError { path: PathBuf },
+    /// Error, if the journal of an interrupted batch could not be resumed, e.g. because it belongs to another batch.
+    JournalError(String),
+    /// Error, if the batch was interrupted by Ctrl-C. Holds the number of files left in place.
+    InterruptedError(usize),
+    /// Error, if some files of the batch could not be moved in `--keep-going` mode. Holds every failed source path with its error.
+    BatchError(Vec<(String, MassMoveError)>),
+}
+
+impl MassMoveError {
+    /// Returns the process exit status for this error: `130` if the batch was interrupted by Ctrl-C,
+    /// `3` if the batch was only partially moved, `1` otherwise. `2` is left to the usage errors reported by clap.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MassMoveError::InterruptedError(_) => 130,
+            MassMoveError::BatchError(_) => 3,
+            _ => 1,
+        }
+    }
 }
 
 /// Implementation of Glob Pattern Error for Mass Mover.
@@ -1,11 +1,30 @@
 extern crate glob;
+use crate::conflict::{backup_dir_path, ConflictPolicy, ConflictPrompt, Resolution};
+use crate::copy::{copy_file, sync_parent, CopyOptions, CopyReport, CopyStrategy, Progress};
 use crate::errors::MassMoveError;
+use crate::journal::{Journal, JOURNAL_PREFIX};
+use crate::links::{is_symlink, read_link_target, SymlinkPolicy};
+use crate::lock::LOCK_NAME;
+use crate::special::{self, file_metadata, is_special, special_kind, SpecialPolicy};
+use crate::sys;
+use crate::trash::trash_file;
+use clap::ValueEnum;
 use glob::glob;
 use regex::Regex;
 use std::{
-    collections::HashSet,
-    fs,
-    path::{Path, PathBuf},
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs::{self, Metadata, OpenOptions},
+    io,
+    iter::Peekable,
+    path::{self, Path, PathBuf},
+    process,
+    str::Chars,
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc, Mutex,
+    },
 };
 
 /// This function searches files in global directory by pattern.
@@ -52,10 +71,8 @@ use std::{
 pub fn capture_files_by_pattern(pattern: &str) -> Result<HashSet<String>, MassMoveError> {
     let mut files_by_pattern: HashSet<String> = HashSet::new();
 
-    for entry in glob(pattern)? {
-        if let Ok(path) = entry {
-            files_by_pattern.insert(path.display().to_string().replace("\\", "/"));
-        }
+    for path in glob(pattern)?.flatten() {
+        files_by_pattern.insert(path.display().to_string().replace("\\", "/"));
     }
 
     if files_by_pattern.is_empty() {
@@ -127,7 +144,7 @@ pub fn rename_file_by_pattern(
         for i in 1..captures.len() {
             let marker = format!("#{}", i);
             let replacement = captures.get(i).map_or("", |m| m.as_str());
-            new_path = new_path.replacen(&marker, &replacement, 1);
+            new_path = new_path.replacen(&marker, replacement, 1);
         }
         Ok(new_path)
     } else {
@@ -139,20 +156,141 @@ pub fn rename_file_by_pattern(
 }
 
 
-/// This function moves the content from one file to other.
+/// What `move_file` has done with a file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MoveOutcome {
+    /// The file was moved to `destination`, which may differ from the requested one after a conflict was resolved.
+    /// `unpreserved` lists the attributes that were lost when the file was copied to another file system or recreated as
+    /// a special file, `strategy` tells how it was copied. It is `None` if the content of the file was not copied.
+    Moved {
+        destination: PathBuf,
+        unpreserved: Vec<String>,
+        strategy: Option<CopyStrategy>,
+    },
+    /// The file was swapped with the existing `destination`.
+    Exchanged { destination: PathBuf },
+    /// The file was left in place because of the conflict policy, or because it would be appended to itself.
+    Skipped,
+    /// The file was left in place because the user quit at the conflict prompt, so the batch should stop.
+    Quit,
+}
+
+/// How a file is transferred to its destination.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Operation {
+    /// Move the file, falling back to copy and delete across devices.
+    #[default]
+    Move,
+    /// Copy the file and keep the source.
+    Copy,
+    /// Create a hard link to the source.
+    Hardlink,
+    /// Create a symbolic link to the absolute path of the source.
+    Symlink,
+    /// Create a copy-on-write clone of the source (Linux file systems with reflink support, e.g. btrfs or XFS).
+    Reflink,
+    /// Append the content of the source to the destination, creating it if needed, and delete the source afterwards.
+    Append,
+    /// Atomically swap the source and the destination if the destination exists, otherwise move the source.
+    Exchange,
+}
+
+/// Options of `move_file`.
+#[derive(Debug, Clone, Default)]
+pub struct MoveOptions {
+    /// How the file is transferred to its destination.
+    pub operation: Operation,
+    /// What to do if the destination file already exists.
+    pub policy: ConflictPolicy,
+    /// Prompt of `ConflictPolicy::Ask` shared by the whole batch. Without it every conflict gets a new prompt on stdin.
+    pub prompt: Option<ConflictPrompt>,
+    /// Directory where destination files are kept before they are overwritten.
+    pub backup_dir: Option<PathBuf>,
+    /// Whether destination files are moved into the trash instead of being overwritten.
+    pub trash: bool,
+    /// Callback reporting the progress of copies to another file system.
+    pub progress: Option<Progress>,
+    /// Whether to verify copies to another file system by their hash before the source is deleted.
+    pub verify: bool,
+    /// What happens to a source file that is a symbolic link.
+    pub symlinks: SymlinkPolicy,
+    /// Whether a moved or copied symbolic link with a relative target is rewritten to still point at the same file.
+    pub rewrite_links: bool,
+    /// What happens to a source file that is a named pipe, a socket or a device node.
+    pub special: SpecialPolicy,
+    /// Copies made earlier in the batch, so further hard links to the same files are linked instead of copied again.
+    pub hard_links: Option<HardLinks>,
+    /// Journal of the batch, which records every copy to another file system before it is renamed into place.
+    pub journal: Option<Arc<Mutex<Journal>>>,
+}
+
+/// The copies made across devices during a batch, by the device and inode of their sources. A file with several hard
+/// links is copied only once, and its other links in the batch are recreated as hard links to that copy.
+#[derive(Debug, Clone, Default)]
+pub struct HardLinks(Arc<Mutex<HashMap<(u64, u64), PathBuf>>>);
+
+impl HardLinks {
+    /// Creates `link` as a hard link to the copy of the file described by `metadata`. Returns `false` if the file was not
+    /// copied yet or the link could not be created, e.g. because the copy is on another file system.
+    fn link(&self, metadata: &Metadata, link: &Path) -> bool {
+        let Some(inode) = inode(metadata) else {
+            return false;
+        };
+        let copies = self.0.lock().unwrap();
+        copies
+            .get(&inode)
+            .is_some_and(|copy| fs::hard_link(copy, link).is_ok())
+    }
+
+    /// Records `copy` as the copy of the file described by `metadata` if the file has other hard links.
+    fn record(&self, metadata: &Metadata, copy: &Path) {
+        if let Some(inode) = inode(metadata).filter(|_| link_count(metadata) > 1) {
+            self.0.lock().unwrap().insert(inode, copy.to_path_buf());
+        }
+    }
+}
+
+/// Returns the device and inode numbers of a file, which are the same for all its hard links.
+pub fn inode(metadata: &Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+fn link_count(metadata: &Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.nlink()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        1
+    }
+}
+
+/// This function moves the content from one file to other, or copies or links it according to `options.operation`.
 /// # Arguments
 /// - `source_file` - the file whose content wil be moved.
 /// - `destination_file` - the file where contenr will be moved.
-/// - `force` - flag for overwriting existing files if they exist.
+/// - `options` - how to transfer the file, what to do if the destination file already exists and where to keep overwritten files.
 /// # Return value
-/// Returns Result<(), MassMoveError> where:
-/// - Returns nothing if function complete succesfully.
+/// Returns Result<MoveOutcome, MassMoveError> where:
+/// - Returns where the file was moved, or that it was skipped, if function complete succesfully.
 /// - Returns error if not
 /// # Example
 /// ```rust
 /// use std::io::Write;
 /// use mmv::errors::MassMoveError;
-/// use mmv::fs_utils::move_file;
+/// use mmv::fs_utils::{move_file, MoveOptions, MoveOutcome};
 /// use std::path::PathBuf;
 /// use std::fs::{File, write};
 /// use tempfile::TempDir;
@@ -161,34 +299,520 @@ pub fn rename_file_by_pattern(
 ///     let dir = TempDir::new().unwrap();
 ///     let source_file = dir.path().join("some_part_filename.txt");
 ///     let destination_file = dir.path().join("changed_part_filename.txt");
-
 ///     let mut source = File::create(&source_file).unwrap();
 ///     writeln!(source, "This is a test file.").unwrap();
 ///
-///     let result = move_file(&source_file, &destination_file, &false);
-///     result
+///     let result = move_file(&source_file, &destination_file, &MoveOptions::default())?;
+///     assert_eq!(
+///         result,
+///         MoveOutcome::Moved { destination: destination_file, unpreserved: vec![], strategy: None }
+///     );
+///     Ok(())
 /// }
 /// ```
 /// # Errors
-/// - `MassMoveError::FileExistsError` - If the destination file already exists and force is not set.
-/// - `MassMoveError::IoError` - For any other I/O error during file operations (e.g., renaming, copying, or deleting).
+/// - `MassMoveError::FileExistsError` - If the destination file already exists and the policy is `ConflictPolicy::Error`.
+/// - `MassMoveError::PermissionDeniedError` - If the process is not allowed to rename the file.
+/// - `MassMoveError::SourceNotFoundError` - If the source file no longer exists.
+/// - `MassMoveError::MissingDirectoryError` - If the directory of the destination does not exist.
+/// - `MassMoveError::ExchangeUnsupportedError` - If the files cannot be exchanged atomically on this file system.
+/// - `MassMoveError::RenameError` - For any other error of renaming the file.
+/// - `MassMoveError::VerificationError` - If `verify` is set and the copy on another file system differs from the source.
+/// - `MassMoveError::IoError` - For any other I/O error during file operations (e.g., copying, or deleting).
+/// # Behavior
+/// - `Operation::Append` never conflicts with an existing destination: several sources may be appended to the same file.
+///   If appending fails, the destination is truncated back to its previous length and the source is kept. A source that
+///   is the destination itself, or another hard link to it, is skipped instead of being appended to itself.
+/// - `Operation::Exchange` swaps the source with an existing destination in a single `renameat2(RENAME_EXCHANGE)` call
+///   instead of resolving the conflict. A missing destination is simply moved to.
+/// - Every other operation except `Operation::Move` creates the new file under a temporary name next to the destination and then
+///   renames it into place, so an overwritten destination is replaced atomically.
+/// - Unless the conflict is resolved by overwriting, the destination is created atomically only if it does not exist
+///   (`renameat2(RENAME_NOREPLACE)` on Linux, `O_EXCL` for copies, see `sys::rename_noreplace`). If another process creates
+///   the destination in the meantime, the conflict is resolved again instead of overwriting its file.
+/// - Only if the file cannot be renamed because the destination is on another file system, it is copied together with its
+///   ownership, permissions, timestamps and extended attributes (see `copy::copy_file`), reporting the progress to `progress`.
+///   The content is cloned with a reflink, copied with `copy_file_range` or read and written, whichever the file systems
+///   support first, and the strategy is returned in `MoveOutcome::Moved`.
+///   The copy is written under the temporary name and renamed into place only after it has been flushed to the disk and,
+///   if `verify` is set, it matches the source byte by byte, so an interrupted copy never appears under the destination name.
+///   The temporary name is unique to the run and recorded in `journal` first, so a resumed batch can delete a
+///   half-written copy. The source is deleted only after the rename.
+///   If the source cannot be deleted, the copy is removed again so the file is never duplicated.
+/// - With `hard_links`, a file with several hard links among the moved files is copied to another file system only once.
+///   Its other links are moved by linking them to that copy, so the link structure is kept.
+/// - A source file that is a symbolic link is left in place with `SymlinkPolicy::Skip`. With `SymlinkPolicy::Preserve`,
+///   `Operation::Move` and `Operation::Copy` transfer the link itself, also across devices, and with `rewrite_links` a relative
+///   target is rewritten to resolve from the new location (see `links::rewrite_target`). With `SymlinkPolicy::Follow` they
+///   transfer a copy of the file the link points to, and a moved link is deleted. The other operations are not affected.
+/// - A source file that is a named pipe, a socket or a device node is left in place with `SpecialPolicy::Skip`. With
+///   `SpecialPolicy::Recreate` it is renamed, or recreated with `mkfifo`/`mknod` when it is moved to another file system,
+///   copied or reflinked (see `special::recreate`), so its content is never read. It cannot be appended. A symbolic link
+///   is checked by the file it points to wherever it is read through, see `follows_links`.
+/// - If `backup_dir` is set, every destination file that is about to be overwritten (or backed up by `ConflictPolicy::Backup`)
+///   is moved into it first, see `conflict::backup_dir_path`. The backup falls back to copying across devices just like the move itself.
+/// - Otherwise, if `trash` is set, every destination file that is about to be overwritten is moved into the trash of the
+///   user first, see `trash::trash_file`. If it cannot be trashed, the move fails and the destination is kept.
 pub fn move_file(
-    source_file: &PathBuf,
-    destination_file: &PathBuf,
-    force: &bool,
+    source_file: &Path,
+    destination_file: &Path,
+    options: &MoveOptions,
+) -> Result<MoveOutcome, MassMoveError> {
+    if options.symlinks == SymlinkPolicy::Skip && is_symlink(source_file)
+        || options.special == SpecialPolicy::Skip
+            && is_special(source_file, follows_links(options.operation, options.symlinks))
+    {
+        return Ok(MoveOutcome::Skipped);
+    }
+    if options.operation == Operation::Append && is_same_file(source_file, destination_file) {
+        return Ok(MoveOutcome::Skipped);
+    }
+    let mut destination_file = destination_file.to_path_buf();
+
+    loop {
+        let mut overwrite = false;
+        let exists = fs::symlink_metadata(&destination_file).is_ok();
+
+        if exists && options.operation == Operation::Exchange {
+            exchange_files(source_file, &destination_file)?;
+            return Ok(MoveOutcome::Exchanged {
+                destination: destination_file,
+            });
+        }
+
+        if exists && options.operation != Operation::Append {
+            let resolution = match &options.prompt {
+                Some(prompt) if options.policy == ConflictPolicy::Ask => {
+                    prompt.resolve(source_file, &destination_file)?
+                }
+                _ => options.policy.resolve(source_file, &destination_file)?,
+            };
+            let backup_file = match resolution {
+                Resolution::Skip => return Ok(MoveOutcome::Skipped),
+                Resolution::Quit => return Ok(MoveOutcome::Quit),
+                Resolution::Overwrite => {
+                    overwrite = true;
+                    None
+                }
+                Resolution::Rename(renamed_file) => {
+                    destination_file = renamed_file;
+                    None
+                }
+                Resolution::Backup(backup_file) => Some(backup_file),
+            };
+
+            let backup_file = match (&options.backup_dir, backup_file) {
+                (Some(backup_dir), _) if destination_file.exists() => {
+                    Some(backup_dir_path(backup_dir, &destination_file))
+                }
+                (_, backup_file) => backup_file,
+            };
+            if let Some(backup_file) = backup_file {
+                if let Some(parent) = backup_file.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                match rename_or_copy(
+                    &destination_file,
+                    &backup_file,
+                    &CopyOptions::default(),
+                    None,
+                    None,
+                ) {
+                    Err(MassMoveError::FileExistsError(_)) => continue,
+                    result => result?,
+                };
+                overwrite = false;
+            } else if overwrite && options.trash {
+                trash_file(&destination_file)?;
+                overwrite = false;
+            }
+        }
+
+        match apply_operation(options, source_file, &destination_file, overwrite) {
+            // Another process created the destination in the meantime, so the conflict is resolved again.
+            Err(MassMoveError::FileExistsError(_)) => continue,
+            result => {
+                let transferred = result?;
+                return Ok(MoveOutcome::Moved {
+                    destination: destination_file,
+                    strategy: transferred.strategy,
+                    unpreserved: transferred.unpreserved,
+                });
+            }
+        }
+    }
+}
+
+/// How the content of a file was transferred, if it was copied or recreated rather than renamed or linked.
+#[derive(Debug, Default)]
+struct Transferred {
+    /// The strategy that copied the content of a regular file.
+    strategy: Option<CopyStrategy>,
+    /// The names of the attributes that could not be preserved on the copy or the recreated node.
+    unpreserved: Vec<String>,
+}
+
+impl From<CopyReport> for Transferred {
+    fn from(report: CopyReport) -> Self {
+        Transferred {
+            strategy: Some(report.strategy),
+            unpreserved: report.unpreserved,
+        }
+    }
+}
+
+fn apply_operation(
+    options: &MoveOptions,
+    source_file: &Path,
+    destination_file: &Path,
+    overwrite: bool,
+) -> Result<Transferred, MassMoveError> {
+    let temporary_file = temporary_path(destination_file);
+    let symlink = is_symlink(source_file);
+    let mut unpreserved = Vec::new();
+    let follow = follows_links(options.operation, options.symlinks);
+    let special = is_special(source_file, follow);
+    let created = match options.operation {
+        // The link cannot simply be renamed: it is replaced by a copy of its target, or recreated with a rewritten target.
+        Operation::Move | Operation::Exchange
+            if symlink && (options.symlinks == SymlinkPolicy::Follow || options.rewrite_links) =>
+        {
+            let transferred = if special {
+                let metadata = fs::metadata(source_file)?;
+                Transferred {
+                    strategy: None,
+                    unpreserved: special::recreate(source_file, &metadata, &temporary_file)?,
+                }
+            } else if options.symlinks == SymlinkPolicy::Follow {
+                let copy_options = CopyOptions {
+                    overwrite: true,
+                    progress: options.progress.clone(),
+                    verify: options.verify,
+                    ..Default::default()
+                };
+                copy_file(source_file, &temporary_file, &copy_options)?.into()
+            } else {
+                let target = read_link_target(source_file, destination_file, true)?;
+                sys::symlink(&target, &temporary_file)?;
+                Transferred::default()
+            };
+            replace_source(source_file, &temporary_file, destination_file, overwrite)?;
+            return Ok(transferred);
+        }
+        Operation::Move | Operation::Exchange => {
+            let copy_options = CopyOptions {
+                overwrite,
+                progress: options.progress.clone(),
+                verify: options.verify,
+                ..Default::default()
+            };
+            return rename_or_copy(
+                source_file,
+                destination_file,
+                &copy_options,
+                options.hard_links.as_ref(),
+                options.journal.as_deref(),
+            );
+        }
+        Operation::Append if special => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A special file cannot be appended to another file.",
+            )
+            .into())
+        }
+        Operation::Append => {
+            return append_file(source_file, destination_file).map(|_| Transferred::default())
+        }
+        Operation::Copy | Operation::Reflink if special => {
+            special::recreate(source_file, &file_metadata(source_file, follow)?, &temporary_file)
+                .map(|report| unpreserved = report)
+        }
+        Operation::Copy if symlink && options.symlinks == SymlinkPolicy::Preserve => {
+            let target = read_link_target(source_file, destination_file, options.rewrite_links)?;
+            sys::symlink(&target, &temporary_file)
+        }
+        Operation::Copy => fs::copy(source_file, &temporary_file).map(|_| ()),
+        Operation::Hardlink => fs::hard_link(source_file, &temporary_file),
+        Operation::Symlink => sys::symlink(&path::absolute(source_file)?, &temporary_file),
+        Operation::Reflink => sys::reflink(source_file, &temporary_file),
+    };
+
+    let result = created
+        .map_err(MassMoveError::from)
+        .and_then(|_| rename_file(&temporary_file, destination_file, overwrite));
+    if let Err(error) = result {
+        let _ = fs::remove_file(&temporary_file);
+        return Err(error);
+    }
+    // Renaming a hard link onto another link of the same file does nothing, so the temporary link may still exist.
+    if fs::symlink_metadata(&temporary_file).is_ok() {
+        fs::remove_file(&temporary_file)?;
+    }
+    Ok(Transferred {
+        strategy: None,
+        unpreserved,
+    })
+}
+
+fn append_file(source_file: &Path, destination_file: &Path) -> Result<(), MassMoveError> {
+    let mut source = fs::File::open(source_file)?;
+    let mut destination = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(destination_file)?;
+    let original_len = destination.metadata()?.len();
+
+    if let Err(error) = io::copy(&mut source, &mut destination).and_then(|_| destination.sync_all())
+    {
+        let _ = destination.set_len(original_len);
+        return Err(error.into());
+    }
+    fs::remove_file(source_file)?;
+    Ok(())
+}
+
+/// Returns whether both paths refer to the same file, following symbolic links.
+fn is_same_file(left: &Path, right: &Path) -> bool {
+    match (fs::metadata(left), fs::metadata(right)) {
+        (Ok(left), Ok(right)) => inode(&left).is_some() && inode(&left) == inode(&right),
+        _ => false,
+    }
+}
+
+/// Returns whether `operation` reads the file a symbolic link points to instead of transferring the link: moves and copies
+/// with `SymlinkPolicy::Follow`, and appends and reflinks, which always open the source through the link.
+pub fn follows_links(operation: Operation, symlinks: SymlinkPolicy) -> bool {
+    match operation {
+        Operation::Move | Operation::Exchange | Operation::Copy => {
+            symlinks == SymlinkPolicy::Follow
+        }
+        Operation::Append | Operation::Reflink => true,
+        Operation::Hardlink | Operation::Symlink => false,
+    }
+}
+
+/// Returns whether `path` is a file created by `mmv` itself: a temporary file, the journal or the lock file of a batch.
+pub fn is_mmv_file(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.starts_with(JOURNAL_PREFIX)
+        || name == LOCK_NAME
+        || (name.starts_with('.') && name.ends_with(".mmv-tmp"))
+}
+
+/// Returns the hidden path `.name.PID.N.mmv-tmp` next to `path`. The process id and a counter make it unique, so
+/// parallel jobs and concurrent runs that write to the same destination never share a temporary file.
+pub fn temporary_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(
+        ".{}.{}.mmv-tmp",
+        process::id(),
+        COUNTER.fetch_add(1, atomic::Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+/// Renames the file, or copies it with its metadata and deletes the source if it is moved to another file system.
+/// The copy is written to the temporary path and renamed into place once it is complete. A symbolic link is recreated
+/// with the same target instead of copying the file it points to, and a special file is recreated as the same kind of node.
+/// If `hard_links` holds a copy of another hard link of the same file, the file is linked to that copy instead.
+/// The temporary file is recorded in `journal` before the copy starts, and the complete copy before it is renamed into
+/// place, so a resumed batch can delete a partial copy or the source.
+/// Unless `overwrite` is set, an existing destination is never replaced. Returns how the file was copied or recreated,
+/// if it was.
+fn rename_or_copy(
+    source_file: &Path,
+    destination_file: &Path,
+    options: &CopyOptions,
+    hard_links: Option<&HardLinks>,
+    journal: Option<&Mutex<Journal>>,
+) -> Result<Transferred, MassMoveError> {
+    match rename_file(source_file, destination_file, options.overwrite) {
+        Ok(_) => Ok(Transferred::default()),
+        Err(MassMoveError::RenameError { error, .. })
+            if error.kind() == io::ErrorKind::CrossesDevices =>
+        {
+            let temporary_file = temporary_path(destination_file);
+            if let Some(journal) = journal {
+                journal.lock().unwrap().record_temporary(
+                    &source_file.display().to_string(),
+                    &temporary_file.display().to_string(),
+                )?;
+            }
+            let metadata = fs::symlink_metadata(source_file)?;
+            let transferred = if metadata.file_type().is_symlink() {
+                sys::symlink(&fs::read_link(source_file)?, &temporary_file)?;
+                Transferred::default()
+            } else if special_kind(&metadata).is_some() {
+                Transferred {
+                    strategy: None,
+                    unpreserved: special::recreate(source_file, &metadata, &temporary_file)?,
+                }
+            } else if hard_links.is_some_and(|hard_links| hard_links.link(&metadata, &temporary_file))
+            {
+                Transferred::default()
+            } else {
+                let copy_options = CopyOptions {
+                    overwrite: true,
+                    ..options.clone()
+                };
+                copy_file(source_file, &temporary_file, &copy_options)?.into()
+            };
+            if let Some(journal) = journal {
+                let recorded = journal.lock().unwrap().record_copy(
+                    &source_file.display().to_string(),
+                    &destination_file.display().to_string(),
+                );
+                if let Err(error) = recorded {
+                    let _ = fs::remove_file(&temporary_file);
+                    return Err(error);
+                }
+            }
+            replace_source(
+                source_file,
+                &temporary_file,
+                destination_file,
+                options.overwrite,
+            )?;
+            if let Some(hard_links) = hard_links.filter(|_| transferred.strategy.is_some()) {
+                hard_links.record(&metadata, destination_file);
+            }
+            Ok(transferred)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Renames the complete `temporary_file` to `destination_file` and deletes `source_file`, finishing a move that could not be
+/// a single rename. If the source cannot be deleted, the destination is removed again so the file is never duplicated.
+fn replace_source(
+    source_file: &Path,
+    temporary_file: &Path,
+    destination_file: &Path,
+    overwrite: bool,
 ) -> Result<(), MassMoveError> {
-    if !force && destination_file.exists() {
-        return Err(MassMoveError::FileExistsError(
-            "The file already exists. Try --force mode to overwrite the file".to_string(),
-        ));
+    let renamed = rename_file(temporary_file, destination_file, overwrite)
+        .and_then(|_| Ok(sync_parent(destination_file)?));
+    if let Err(error) = renamed {
+        let _ = fs::remove_file(temporary_file);
+        return Err(error);
+    }
+    if let Err(error) = fs::remove_file(source_file) {
+        let _ = fs::remove_file(destination_file);
+        return Err(error.into());
+    }
+    Ok(())
+}
+
+fn exchange_files(source_file: &Path, destination_file: &Path) -> Result<(), MassMoveError> {
+    sys::rename_exchange(source_file, destination_file).map_err(|error| match error.kind() {
+        io::ErrorKind::Unsupported | io::ErrorKind::InvalidInput => {
+            MassMoveError::ExchangeUnsupportedError {
+                source: source_file.to_path_buf(),
+                destination: destination_file.to_path_buf(),
+            }
+        }
+        _ => rename_error(error, source_file, destination_file),
+    })
+}
+
+/// Renames the file, atomically refusing to replace an existing destination unless `overwrite` is set.
+fn rename_file(
+    source_file: &Path,
+    destination_file: &Path,
+    overwrite: bool,
+) -> Result<(), MassMoveError> {
+    let renamed = if overwrite {
+        fs::rename(source_file, destination_file)
+    } else {
+        sys::rename_noreplace(source_file, destination_file)
+    };
+    renamed.map_err(|error| rename_error(error, source_file, destination_file))
+}
+
+/// Turns an error of `fs::rename` into the `MassMoveError` variant that describes it best.
+fn rename_error(error: io::Error, source_file: &Path, destination_file: &Path) -> MassMoveError {
+    let source = source_file.to_path_buf();
+    let destination = destination_file.to_path_buf();
+    match error.kind() {
+        io::ErrorKind::AlreadyExists => MassMoveError::FileExistsError(format!(
+            "The file {} already exists.",
+            destination_file.display()
+        )),
+        io::ErrorKind::PermissionDenied => MassMoveError::PermissionDeniedError {
+            source,
+            destination,
+        },
+        io::ErrorKind::NotFound if fs::symlink_metadata(source_file).is_err() => {
+            MassMoveError::SourceNotFoundError {
+                source,
+                destination,
+            }
+        }
+        io::ErrorKind::NotFound => MassMoveError::MissingDirectoryError {
+            source,
+            destination,
+        },
+        _ => MassMoveError::RenameError {
+            source,
+            destination,
+            error,
+        },
     }
+}
+
+/// This function compares two strings in natural order, i.e. runs of digits are compared by their numeric value.
+/// # Return value
+/// Returns the `Ordering` of `left` and `right`, so that `part2` comes before `part10`.
+/// Numbers that differ only by leading zeros are ordered by the number of zeros.
+/// # Example
+/// ```rust
+/// use mmv::fs_utils::natural_cmp;
+///
+/// let mut files = vec!["app_part10.log", "app_part2.log", "app_part1.log"];
+/// files.sort_by(|left, right| natural_cmp(left, right));
+/// assert_eq!(files, vec!["app_part1.log", "app_part2.log", "app_part10.log"]);
+/// ```
+pub fn natural_cmp(left: &str, right: &str) -> Ordering {
+    let mut left_chars = left.chars().peekable();
+    let mut right_chars = right.chars().peekable();
 
-    match fs::rename(&source_file, &destination_file) {
-        Ok(_) => Ok(()),
-        Err(_) => {
-            fs::copy(&source_file, &destination_file)?;
-            fs::remove_file(&source_file)?;
-            Ok(())
+    loop {
+        let ordering = match (left_chars.peek(), right_chars.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) if l.is_ascii_digit() && r.is_ascii_digit() => {
+                let left_number = take_digits(&mut left_chars);
+                let right_number = take_digits(&mut right_chars);
+                let left_trimmed = left_number.trim_start_matches('0');
+                let right_trimmed = right_number.trim_start_matches('0');
+                left_trimmed
+                    .len()
+                    .cmp(&right_trimmed.len())
+                    .then_with(|| left_trimmed.cmp(right_trimmed))
+                    .then_with(|| left_number.len().cmp(&right_number.len()))
+            }
+            (Some(l), Some(r)) => {
+                let ordering = l.cmp(r);
+                left_chars.next();
+                right_chars.next();
+                ordering
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
         }
     }
 }
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(digit);
+    }
+    digits
+}
@@ -0,0 +1,203 @@
+use crate::errors::MassMoveError;
+use clap::ValueEnum;
+use std::{
+    io::{self, BufRead, StdinLock, Stdout, Write},
+    path::Path,
+};
+
+/// When the interactive mode asks the user for a confirmation.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InteractiveMode {
+    /// Ask before every rename.
+    Always,
+    /// Ask only if the destination file already exists.
+    Overwrite,
+}
+
+/// The user's decision about a single rename.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Move the file to `destination`. `overwrite` is set if the user allowed to overwrite an existing file.
+    Move {
+        destination: String,
+        overwrite: bool,
+    },
+    /// Leave the file in place.
+    Skip,
+    /// Leave this and all remaining files in place.
+    Quit,
+}
+
+enum Answer {
+    Yes,
+    No,
+    All,
+    Quit,
+    Edit(String),
+}
+
+/// Asks the user to confirm every computed rename.
+/// # Example
+/// ```rust
+/// use mmv::interactive::{Decision, Interactive, InteractiveMode};
+/// use std::io::Cursor;
+///
+/// let mut output = Vec::new();
+/// let mut interactive = Interactive::new(InteractiveMode::Always, Cursor::new("n\ne\nnew.txt\ny\n"), &mut output);
+///
+/// assert_eq!(interactive.confirm("a.txt", "b.txt").unwrap(), Decision::Skip);
+/// assert_eq!(
+///     interactive.confirm("a.txt", "b.txt").unwrap(),
+///     Decision::Move { destination: "new.txt".to_string(), overwrite: false }
+/// );
+/// ```
+pub struct Interactive<R, W> {
+    mode: InteractiveMode,
+    input: R,
+    output: W,
+    confirmed_all: bool,
+    confirmed_overwrites: bool,
+}
+
+impl Interactive<StdinLock<'static>, Stdout> {
+    /// Creates a prompt reading answers from stdin and writing questions to stdout.
+    pub fn stdio(mode: InteractiveMode) -> Self {
+        Interactive::new(mode, io::stdin().lock(), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> Interactive<R, W> {
+    /// Creates a prompt reading answers from `input` and writing questions to `output`.
+    pub fn new(mode: InteractiveMode, input: R, output: W) -> Self {
+        Interactive {
+            mode,
+            input,
+            output,
+            confirmed_all: false,
+            confirmed_overwrites: false,
+        }
+    }
+
+    /// This function asks the user whether `source` should be moved to `destination`.
+    /// # Return value
+    /// Returns `Result<Decision, MassMoveError>`, where:
+    /// - If succesful - returns the decision of the user. Editing the name asks again about the new name.
+    /// - In case of an error, a `MassMoveError::StdIOError` occurs if the prompt could not be read or written.
+    /// # Behavior
+    /// - In `InteractiveMode::Overwrite` the user is asked only if the destination exists.
+    /// - After the `[a]ll` answer no more questions are asked about renames to new files. Overwriting an existing file is
+    ///   still asked unless `[a]ll` was the answer to an overwrite.
+    /// - The end of the input and Ctrl-C (see `sys::catch_interrupts`) are treated as `[q]uit`.
+    /// - An edited name that is a relative path is resolved from the directory of the destination, not from the current
+    ///   directory, so `report_v2.txt` renames `out/report.txt` to `out/report_v2.txt`.
+    pub fn confirm(&mut self, source: &str, destination: &str) -> Result<Decision, MassMoveError> {
+        let mut destination = destination.to_string();
+        loop {
+            let exists = Path::new(&destination).exists();
+            let confirmed = if exists {
+                self.confirmed_overwrites
+            } else {
+                self.confirmed_all || self.mode == InteractiveMode::Overwrite
+            };
+            if confirmed {
+                return Ok(Decision::Move {
+                    destination,
+                    overwrite: exists,
+                });
+            }
+
+            match self.ask(source, &destination, exists)? {
+                Answer::Yes => {
+                    return Ok(Decision::Move {
+                        destination,
+                        overwrite: exists,
+                    })
+                }
+                Answer::No => return Ok(Decision::Skip),
+                Answer::Quit => return Ok(Decision::Quit),
+                Answer::All => {
+                    self.confirmed_all = true;
+                    self.confirmed_overwrites |= exists;
+                    return Ok(Decision::Move {
+                        destination,
+                        overwrite: exists,
+                    });
+                }
+                Answer::Edit(name) => destination = edited_path(&destination, &name),
+            }
+        }
+    }
+
+    fn ask(
+        &mut self,
+        source: &str,
+        destination: &str,
+        exists: bool,
+    ) -> Result<Answer, MassMoveError> {
+        let overwrite = if exists { " (overwrite)" } else { "" };
+        loop {
+            write!(
+                self.output,
+                "{} -> {}{}? [y]es / [n]o / [a]ll / [q]uit / [e]dit name: ",
+                source, destination, overwrite
+            )?;
+            let answer = match self.read_line()? {
+                Some(answer) => answer.to_lowercase(),
+                None => return Ok(Answer::Quit),
+            };
+
+            match answer.as_str() {
+                "y" | "yes" => return Ok(Answer::Yes),
+                "n" | "no" => return Ok(Answer::No),
+                "a" | "all" => return Ok(Answer::All),
+                "q" | "quit" => return Ok(Answer::Quit),
+                "e" | "edit" => {
+                    write!(self.output, "New name: ")?;
+                    match self.read_line()? {
+                        Some(name) if !name.is_empty() => return Ok(Answer::Edit(name)),
+                        Some(_) => continue,
+                        None => return Ok(Answer::Quit),
+                    }
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Reads a line without retrying on `EINTR` like `BufRead::read_line` does, so Ctrl-C at the prompt quits.
+    fn read_line(&mut self) -> Result<Option<String>, MassMoveError> {
+        self.output.flush()?;
+        let mut line = Vec::new();
+        loop {
+            let available = match self.input.fill_buf() {
+                Ok(available) => available,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => return Ok(None),
+                Err(error) => return Err(error.into()),
+            };
+            if available.is_empty() {
+                break;
+            }
+            let (length, complete) = match available.iter().position(|&byte| byte == b'\n') {
+                Some(end) => (end + 1, true),
+                None => (available.len(), false),
+            };
+            line.extend_from_slice(&available[..length]);
+            self.input.consume(length);
+            if complete {
+                break;
+            }
+        }
+        if line.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&line).trim().to_string()))
+    }
+}
+
+/// Returns the path of the edited `name` of `destination`: a relative name is placed in the directory of `destination`.
+fn edited_path(destination: &str, name: &str) -> String {
+    match Path::new(destination).parent() {
+        Some(parent) if Path::new(name).is_relative() => parent.join(name).display().to_string(),
+        _ => name.to_string(),
+    }
+}
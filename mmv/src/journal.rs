@@ -0,0 +1,262 @@
+use crate::copy::same_content;
+use crate::errors::MassMoveError;
+use crate::links::{absolute, normalize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// Prefix of the journal files created in the destination directory, followed by a hash of the batch.
+pub const JOURNAL_PREFIX: &str = ".mmv-journal";
+
+const HEADER: &str = "mmv journal";
+const DONE: &str = "done";
+const COPIED: &str = "copied";
+const TEMPORARY: &str = "temporary";
+
+/// Records which files of a batch are already done, so an interrupted batch can be resumed.
+///
+/// The journal is a text file whose first line identifies the batch by its patterns and whose following lines
+/// hold one `kind<TAB>source<TAB>destination` entry per file. `done` entries are written after a file is moved, and
+/// `copied` entries after a copy to another file system is complete but before it is renamed into place. `temporary`
+/// entries name the temporary file such a copy is written to before it is started, so a resumed batch can delete it.
+/// The file is only created by the first entry that is written, see `Journal::start`.
+/// # Example
+/// ```rust
+/// use mmv::errors::MassMoveError;
+/// use mmv::journal::Journal;
+/// use tempfile::TempDir;
+///
+/// fn main() -> Result<(), MassMoveError> {
+///     let dir = TempDir::new()?;
+///     let path = dir.path().join(".mmv-journal");
+///
+///     let mut journal = Journal::open(&path, "a_*.txt", "b_#1.txt", false)?;
+///     journal.record("a_1.txt", "b_1.txt")?;
+///     assert!(!path.exists());
+///     journal.record_copy("a_2.txt", "b_2.txt")?;
+///     journal.record("a_2.txt", "b_2.txt")?;
+///
+///     let journal = Journal::open(&path, "a_*.txt", "b_#1.txt", true)?;
+///     assert!(journal.is_done("a_2.txt", "b_2.txt"));
+///     assert!(!journal.is_done("a_3.txt", "b_3.txt"));
+///     journal.finish()?;
+///     assert!(!path.exists());
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    header: String,
+    file: Option<File>,
+    done: HashSet<(String, String)>,
+    copied: HashMap<String, String>,
+    temporaries: HashMap<String, String>,
+}
+
+impl Journal {
+    /// This function opens the journal of the batch defined by `source_pattern` and `destination_pattern`.
+    /// # Arguments
+    /// - `path` - the journal file, see `Journal::path_for`.
+    /// - `source_pattern`, `destination_pattern` - the patterns of the batch.
+    /// - `resume` - whether to continue the recorded batch.
+    /// # Return value
+    /// Returns `Result<Journal, MassMoveError>`, where:
+    /// - If succesful - returns the journal with all entries recorded so far. It is empty if the file does not exist.
+    /// - In case of an error, a `MassMoveError::JournalError` occurs if the journal exists and `resume` is not set, or
+    ///   if it belongs to another batch or is damaged, or a `MassMoveError::StdIOError` if it could not be read.
+    pub fn open(
+        path: &Path,
+        source_pattern: &str,
+        destination_pattern: &str,
+        resume: bool,
+    ) -> Result<Journal, MassMoveError> {
+        let mut journal = Journal {
+            path: path.to_path_buf(),
+            header: format!("{}\t{}\t{}", HEADER, source_pattern, destination_pattern),
+            file: None,
+            done: HashSet::new(),
+            copied: HashMap::new(),
+            temporaries: HashMap::new(),
+        };
+        if !path.exists() {
+            return Ok(journal);
+        }
+        if !resume {
+            return Err(MassMoveError::JournalError(format!(
+                "The batch was interrupted before, finish it with --resume or remove its journal {}.",
+                path.display()
+            )));
+        }
+
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        if lines.next().transpose()?.as_deref() != Some(journal.header.as_str()) {
+            return Err(MassMoveError::JournalError(format!(
+                "The journal {} belongs to another batch.",
+                path.display()
+            )));
+        }
+        for line in lines {
+            let line = line?;
+            // The last line may be cut off if the batch was interrupted while writing it.
+            let mut fields = line.splitn(3, '\t');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(DONE), Some(source), Some(destination)) => {
+                    journal
+                        .done
+                        .insert((source.to_string(), destination.to_string()));
+                }
+                (Some(COPIED), Some(source), Some(destination)) => {
+                    journal
+                        .copied
+                        .insert(source.to_string(), destination.to_string());
+                }
+                (Some(TEMPORARY), Some(source), Some(temporary)) => {
+                    journal
+                        .temporaries
+                        .insert(source.to_string(), temporary.to_string());
+                }
+                _ => {}
+            }
+        }
+        journal.file = Some(OpenOptions::new().append(true).open(path)?);
+        Ok(journal)
+    }
+
+    /// Returns the journal path for a batch: `.mmv-journal-HASH` in the deepest directory of `destination_pattern` that
+    /// does not depend on the source file, i.e. contains no `#` markers. The hash of the absolute patterns keeps the
+    /// journals of different batches into the same directory apart.
+    /// # Example
+    /// ```rust
+    /// use mmv::journal::Journal;
+    /// use std::path::Path;
+    ///
+    /// let path = Journal::path_for("/srv/new/*.csv", "/srv/done/#1/data.csv");
+    /// assert_eq!(path.parent(), Some(Path::new("/srv/done")));
+    /// assert_ne!(path, Journal::path_for("/srv/old/*.csv", "/srv/done/#1/data.csv"));
+    /// ```
+    pub fn path_for(source_pattern: &str, destination_pattern: &str) -> PathBuf {
+        let directory: PathBuf = Path::new(destination_pattern)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .components()
+            .take_while(|component| !component.as_os_str().to_string_lossy().contains('#'))
+            .collect();
+        // FNV-1a, which unlike the hasher of the standard library is stable across Rust versions.
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for pattern in [source_pattern, destination_pattern] {
+            let pattern = normalize(&absolute(Path::new(pattern)));
+            for &byte in pattern.as_os_str().as_encoded_bytes().iter().chain(&[0]) {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+            }
+        }
+        directory.join(format!("{}-{:016x}", JOURNAL_PREFIX, hash))
+    }
+
+    /// Returns whether moving `source` to `destination` was recorded as done.
+    pub fn is_done(&self, source: &str, destination: &str) -> bool {
+        self.done
+            .contains(&(source.to_string(), destination.to_string()))
+    }
+
+    /// Returns the destination a complete copy of `source` was recorded for, see `Journal::record_copy`.
+    pub fn copied_to(&self, source: &str) -> Option<&str> {
+        self.copied.get(source).map(String::as_str)
+    }
+
+    /// Returns the temporary file a copy of `source` was recorded to be written to, see `Journal::record_temporary`.
+    pub fn temporary_of(&self, source: &str) -> Option<&str> {
+        self.temporaries.get(source).map(String::as_str)
+    }
+
+    /// This function creates the journal file, so every following `record` is written to it.
+    /// It does nothing if the file already exists.
+    pub fn start(&mut self) -> Result<(), MassMoveError> {
+        if self.file.is_none() {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&self.path)?;
+            writeln!(file, "{}", self.header)?;
+            file.sync_all()?;
+            self.file = Some(file);
+        }
+        Ok(())
+    }
+
+    /// This function records that `source` was moved to `destination` and flushes the record to the disk.
+    /// Nothing is written until the journal is started, so batches of plain renames leave no journal behind.
+    pub fn record(&mut self, source: &str, destination: &str) -> Result<(), MassMoveError> {
+        if self.file.is_some() {
+            self.write(DONE, source, destination)?;
+        }
+        self.done
+            .insert((source.to_string(), destination.to_string()));
+        Ok(())
+    }
+
+    /// This function starts the journal and records that `destination` holds a complete copy of `source`. It is
+    /// called before the copy is renamed into place, so a resumed batch may delete the source only if this entry exists.
+    pub fn record_copy(&mut self, source: &str, destination: &str) -> Result<(), MassMoveError> {
+        self.start()?;
+        self.write(COPIED, source, destination)?;
+        self.copied
+            .insert(source.to_string(), destination.to_string());
+        Ok(())
+    }
+
+    /// This function starts the journal and records that a copy of `source` is about to be written to `temporary`. It
+    /// is called before the copy is started, so a resumed batch can delete the temporary file of an interrupted copy.
+    pub fn record_temporary(&mut self, source: &str, temporary: &str) -> Result<(), MassMoveError> {
+        self.start()?;
+        self.write(TEMPORARY, source, temporary)?;
+        self.temporaries
+            .insert(source.to_string(), temporary.to_string());
+        Ok(())
+    }
+
+    fn write(&mut self, kind: &str, source: &str, destination: &str) -> Result<(), MassMoveError> {
+        if let Some(file) = &mut self.file {
+            writeln!(file, "{}\t{}\t{}", kind, source, destination)?;
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// This function removes the journal after the whole batch is done, if it was created.
+    pub fn finish(self) -> Result<(), MassMoveError> {
+        if let Some(file) = self.file {
+            drop(file);
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// This function checks whether `destination` is a finished copy of `source`, which is left behind if a batch was
+/// interrupted after the copy was renamed into place but before the source was deleted.
+/// # Return value
+/// Returns `io::Result<bool>`, where:
+/// - If succesful - returns `true` if both are regular files with the same content.
+/// - In case of an error, the OS error is returned if one of the files could not be read.
+/// # Behavior
+/// Only call this for a pair recorded by `Journal::record_copy`: two unrelated files may well have the same content.
+pub fn is_finished_copy(source: &Path, destination: &Path) -> io::Result<bool> {
+    let (Ok(source_metadata), Ok(destination_metadata)) = (
+        fs::symlink_metadata(source),
+        fs::symlink_metadata(destination),
+    ) else {
+        return Ok(false);
+    };
+    if !source_metadata.is_file()
+        || !destination_metadata.is_file()
+        || source_metadata.len() != destination_metadata.len()
+    {
+        return Ok(false);
+    }
+
+    same_content(source, destination)
+}
@@ -19,6 +19,23 @@
 ///
 /// This module ensures that command-line input is correctly handled and validated before being used in the core file moving logic.
 pub mod args;
+/// This module defines how the `mmv` application resolves conflicts with destination files that already exist.
+///
+/// # Enums:
+/// - `ConflictPolicy`: The policy selected by `--on-conflict`. Besides refusing (`error`) and overwriting (`overwrite`),
+///   it can skip the file, move it to a free `name_N.ext`, keep the newer or the larger file, keep the old file as a
+///   `name~` / `name.~N~` backup, or ask the user.
+/// - `Resolution`: The decision taken by a policy for one particular conflict, which is then carried out by `fs_utils::move_file`.
+pub mod conflict;
+/// This module implements the copy fallback used when a file cannot simply be renamed, e.g. when it is moved to another file system.
+///
+/// The `copy` module copies the content of a file and then carries over everything the process is allowed to set:
+/// ownership, permissions, access and modification times, extended attributes and POSIX ACLs. The attributes that
+/// could not be preserved are reported back, so a moved file is indistinguishable from a renamed one whenever possible.
+///
+/// The content is copied with the fastest strategy the file systems support: a `FICLONE` reflink, then `copy_file_range`,
+/// then plain reads and writes. The last two skip the holes of sparse files. The strategy used is reported as `CopyStrategy`.
+pub mod copy;
 /// This module defines custom error types for handling various failure scenarios within the application.
 ///
 /// The `errors` module provides custom error handling for the `mmv` (mass mover) program. It defines different error types that cover all possible issues encountered during file pattern matching, renaming, and moving operations.
@@ -29,6 +46,7 @@ pub mod args;
 ///     - `NotFoundError`: Raised when no files match the provided source pattern.
 ///     - `MatchError`: Raised when the source pattern does not match any part of the file or if renaming fails.
 ///     - `FileExistsError`: Raised when a file already exists in the destination and the `force` option is not enabled.
+///     - `PermissionDeniedError`, `SourceNotFoundError`, `MissingDirectoryError`, `RenameError`: Raised when a file cannot be renamed; they carry both the source and the destination path.
 /// # Usage:
 /// - The `MassMoveError` enum provides a centralized way of handling errors in the application.
 /// - When an error occurs, the appropriate variant is returned and can be matched on to handle specific cases or propagate the error up the stack.
@@ -58,6 +76,43 @@ pub mod errors;
 /// - The function will stop and return the first error encountered (e.g., if one of the files cannot be renamed or moved).
 /// - If `force` is set to `true`, existing files in the destination directory will be overwritten.
 pub mod fs_utils;
+/// This module implements the interactive confirmation mode of the `mmv` application.
+///
+/// The `interactive` module asks the user about every computed rename with the choices
+/// `[y]es / [n]o / [a]ll / [q]uit / [e]dit name`. With `InteractiveMode::Overwrite` the user is asked
+/// only when the destination file already exists, which is a middle ground between refusing to overwrite and `--force`.
+///
+/// # Structs:
+/// - `Interactive`: Reads answers from any `BufRead` and writes questions to any `Write`, so it can be driven by stdin or by tests.
+/// - `Decision`: The outcome of a confirmation: move (possibly to an edited name), skip, or quit.
+pub mod interactive;
+/// This module keeps the journal that makes interrupted batches resumable.
+///
+/// The `journal` module writes a `.mmv-journal-HASH` file, named after the patterns of the batch, into the destination
+/// directory once a file is copied to another file system (or from the start with `--journal`), and appends every
+/// file of the batch as soon as it is moved. Complete copies are recorded before they are renamed into place.
+/// The journal is removed when the whole batch succeeds. If the batch is interrupted or fails, `mmv --resume` with the
+/// same patterns skips the files recorded as done, deletes the sources of recorded copies and finishes the rest.
+///
+/// # Structs:
+/// - `Journal`: Opens, appends to and removes the journal of a batch.
+pub mod journal;
+/// This module defines how the `mmv` application treats source files that are symbolic links.
+///
+/// The `links` module provides the `SymlinkPolicy` chosen with `--symlinks`: a link can be moved itself, replaced by
+/// the file it points to, or left in place. It also computes the target a moved link with a relative target needs to
+/// keep pointing at the same file, which `--rewrite-links` applies. After a batch, `fix_links` retargets the links elsewhere
+/// in a directory tree that pointed at the moved files, which `--fix-links` applies.
+pub mod links;
+/// This module implements `--lock`, which keeps concurrent `mmv` runs over the same directory tree from interfering.
+///
+/// The `lock` module takes an exclusive advisory lock (`flock`) on a lock file for the whole batch, by default
+/// `.mmv-lock` in the common parent directory of the source and destination patterns. A second run fails at once,
+/// or waits for the lock up to `--wait SECONDS`.
+///
+/// # Structs:
+/// - `Lock`: The held lock, released when it is dropped.
+pub mod lock;
 /// This module provides the core functionality for the `mmv` (mass mover) application.
 /// It handles the process of finding, renaming, and moving files based on user-defined patterns.
 ///
@@ -103,3 +158,44 @@ pub mod fs_utils;
 /// - Errors during file renaming or moving (such as permission issues or file system errors).
 /// - Graceful handling of file conflicts (when `force` is not enabled and the destination file already exists).
 pub mod mmv;
+/// This module finds the files that are open in other processes, so `--skip-open` does not move files that are still in use.
+///
+/// The `open_files` module scans the file descriptors in `/proc/*/fd` once and looks up the captured files by their
+/// device and inode, e.g. to keep a log file in place while a daemon still writes to it.
+///
+/// # Structs:
+/// - `OpenFiles`: The files open in other processes, with the processes holding them.
+/// - `OpenFilePolicy`: Whether open files are skipped with a warning or fail the whole batch.
+pub mod open_files;
+/// This module records where moved files came from, so a batch can be undone without its journal.
+///
+/// The `origin` module implements `--tag-origin`: the original path of every moved file and the time of the move are
+/// written into its `user.mmv.origin` extended attribute, which travels with the file, also when it is copied to another
+/// file system. `mmv restore PATTERN` reads the attribute and moves the files back.
+///
+/// # Structs:
+/// - `Origin`: The original path and the timestamp recorded on a moved file.
+pub mod origin;
+/// This module rewrites textual references to moved files, e.g. image paths in Markdown, HTML or CSS files.
+///
+/// The `refs` module finds the old paths of moved files, relative to each text file, and their old names in the files
+/// matching `--update-refs`, and computes the new content. The changes can be printed as a diff or written to the files.
+///
+/// # Structs:
+/// - `ReferenceUpdate`: The old and new content of a text file and the number of rewritten references.
+pub mod refs;
+/// This module defines how the `mmv` application treats source files that are named pipes, sockets or device nodes.
+///
+/// Copying such a file would read from it, which blocks forever on a named pipe without a writer. The `special` module
+/// provides the `SpecialPolicy` chosen with `--special`: special files are skipped with a warning by default, or recreated
+/// at the destination with `mkfifo`/`mknod` when the process is allowed to.
+pub mod special;
+/// This module moves files into the trash of the user, following the freedesktop.org Trash specification.
+///
+/// The `trash` module implements `--trash`: destination files that would be overwritten are moved into
+/// `$XDG_DATA_HOME/Trash/files`, or into the `.Trash/$UID` or `.Trash-$UID` directory of their mount point, together with a
+/// `.trashinfo` file recording their original path and the deletion date, so desktop file managers can restore them.
+pub mod trash;
+/// This module contains thin wrappers around platform-specific system calls used by the `fs_utils` module,
+/// such as copy-on-write clones (reflinks) and symbolic links.
+pub mod sys;
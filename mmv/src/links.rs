@@ -0,0 +1,212 @@
+use crate::errors::MassMoveError;
+use crate::fs_utils::temporary_path;
+use crate::sys;
+use clap::ValueEnum;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{self, Component, Path, PathBuf},
+};
+
+/// What happens to source files that are symbolic links.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Move or copy the link itself, keeping its target.
+    #[default]
+    Preserve,
+    /// Move or copy the file the link points to. A moved link is replaced by a copy of that file.
+    Follow,
+    /// Leave symbolic links in place.
+    Skip,
+}
+
+/// Returns whether `path` itself is a symbolic link.
+pub fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// This function computes the target a symbolic link needs after it is moved from `link` to `new_link`, so it still resolves
+/// to the same file.
+/// # Return value
+/// Returns an absolute `target` unchanged, and a relative `target` rewritten relative to the directory of `new_link`.
+/// # Example
+/// ```rust
+/// use mmv::links::rewrite_target;
+/// use std::path::{Path, PathBuf};
+///
+/// let target = rewrite_target(
+///     Path::new("../data/report.txt"),
+///     Path::new("/home/user/links/report.txt"),
+///     Path::new("/home/user/archive/2024/report.txt"),
+/// );
+/// assert_eq!(target, PathBuf::from("../../data/report.txt"));
+/// ```
+/// # Behavior
+/// The paths are resolved lexically: a `..` after a directory that is itself a symbolic link is not followed.
+pub fn rewrite_target(target: &Path, link: &Path, new_link: &Path) -> PathBuf {
+    if target.is_absolute() {
+        return target.to_path_buf();
+    }
+    let resolved = normalize(&parent(link).join(target));
+    relative_path(&normalize(&parent(new_link)), &resolved)
+}
+
+/// This function reads the target of the symbolic link `link`, rewritten with `rewrite_target` for `new_link` if `rewrite` is set.
+pub fn read_link_target(link: &Path, new_link: &Path, rewrite: bool) -> io::Result<PathBuf> {
+    let target = fs::read_link(link)?;
+    Ok(if rewrite {
+        rewrite_target(&target, link, new_link)
+    } else {
+        target
+    })
+}
+
+/// A symbolic link retargeted by `fix_links`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedLink {
+    /// The symbolic link.
+    pub link: PathBuf,
+    /// The target of the link before it was fixed.
+    pub old_target: PathBuf,
+    /// The new target of the link.
+    pub new_target: PathBuf,
+}
+
+/// This function retargets every symbolic link under `root` that points at a moved file to the file's new path.
+/// # Arguments
+/// - `root` - the directory tree to scan.
+/// - `moved` - the moved files as `(source, destination)` pairs.
+/// # Return value
+/// Returns `Result<Vec<FixedLink>, MassMoveError>`, where:
+/// - If succesful - returns the fixed links in the order they were found.
+/// - In case of an error, a `MassMoveError::StdIOError` occurs if the tree could not be read or a link could not be replaced.
+/// # Example
+/// ```rust
+/// use mmv::errors::MassMoveError;
+/// use mmv::links::fix_links;
+/// use std::{fs, os::unix::fs::symlink, path::PathBuf};
+/// use tempfile::TempDir;
+///
+/// fn main() -> Result<(), MassMoveError> {
+///     let dir = TempDir::new()?;
+///     fs::write(dir.path().join("new.txt"), "content")?;
+///     symlink("old.txt", dir.path().join("link.txt"))?;
+///
+///     let moved = vec![(dir.path().join("old.txt"), dir.path().join("new.txt"))];
+///     let fixed = fix_links(dir.path(), &moved)?;
+///     assert_eq!(fixed.len(), 1);
+///     assert_eq!(fs::read_link(dir.path().join("link.txt"))?, PathBuf::from("new.txt"));
+///     Ok(())
+/// }
+/// ```
+/// # Behavior
+/// - A relative target stays relative to the directory of the link, an absolute target stays absolute.
+/// - Each link is replaced atomically by renaming a new link over it. Symbolic links to directories are not descended into.
+/// - Paths are compared lexically, see `rewrite_target`.
+pub fn fix_links(
+    root: &Path,
+    moved: &[(PathBuf, PathBuf)],
+) -> Result<Vec<FixedLink>, MassMoveError> {
+    let destinations: HashMap<PathBuf, PathBuf> = moved
+        .iter()
+        .map(|(source, destination)| {
+            (
+                normalize(&absolute(source)),
+                normalize(&absolute(destination)),
+            )
+        })
+        .collect();
+
+    let mut fixed = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                directories.push(entry.path());
+                continue;
+            }
+            if !file_type.is_symlink() {
+                continue;
+            }
+
+            let link = entry.path();
+            let old_target = fs::read_link(&link)?;
+            let resolved = normalize(&parent(&link).join(&old_target));
+            let Some(destination) = destinations.get(&resolved) else {
+                continue;
+            };
+            let new_target = if old_target.is_absolute() {
+                destination.clone()
+            } else {
+                relative_path(&normalize(&parent(&link)), destination)
+            };
+
+            let temporary_link = temporary_path(&link);
+            let _ = fs::remove_file(&temporary_link);
+            sys::symlink(&new_target, &temporary_link)?;
+            if let Err(error) = fs::rename(&temporary_link, &link) {
+                let _ = fs::remove_file(&temporary_link);
+                return Err(error.into());
+            }
+            fixed.push(FixedLink {
+                link,
+                old_target,
+                new_target,
+            });
+        }
+    }
+    Ok(fixed)
+}
+
+/// Returns `path` made absolute against the current directory, or unchanged if the current directory is unknown.
+pub fn absolute(path: &Path) -> PathBuf {
+    path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Returns the absolute directory containing `path`.
+fn parent(path: &Path) -> PathBuf {
+    let path = absolute(path);
+    path.parent().map(Path::to_path_buf).unwrap_or(path)
+}
+
+/// Removes `.` and resolves `..` components without accessing the file system.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Returns the relative path leading from the directory `from` to `to`. Both paths must be absolute and normalized.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from
+        .iter()
+        .zip(&to)
+        .take_while(|(left, right)| left == right)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
@@ -0,0 +1,127 @@
+use crate::errors::MassMoveError;
+use crate::links::{absolute, normalize};
+use crate::sys;
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    path::{Component, Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Name of the lock file created by `--lock` without a path.
+pub const LOCK_NAME: &str = ".mmv-lock";
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An exclusive advisory lock (`flock`) on a lock file, held until it is dropped.
+#[derive(Debug)]
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// This function takes the exclusive lock on `path`, creating the lock file if needed.
+    /// # Arguments
+    /// - `path` - the lock file.
+    /// - `wait` - how long to wait for another process to release the lock. Without it the call fails at once.
+    /// # Return value
+    /// Returns `Result<Lock, MassMoveError>`, where:
+    /// - If succesful - returns the lock, which is released when it is dropped or the process exits.
+    /// - In case of an error, a `MassMoveError::LockError` occurs if another process still holds the lock after `wait`,
+    ///   or a `MassMoveError::StdIOError` if the lock file could not be opened.
+    /// # Example
+    /// ```rust
+    /// use mmv::errors::MassMoveError;
+    /// use mmv::lock::Lock;
+    /// use std::time::Duration;
+    /// use tempfile::TempDir;
+    ///
+    /// fn main() -> Result<(), MassMoveError> {
+    ///     let dir = TempDir::new()?;
+    ///     let path = dir.path().join(".mmv-lock");
+    ///
+    ///     let lock = Lock::acquire(&path, None)?;
+    ///     assert!(matches!(
+    ///         Lock::acquire(&path, Some(Duration::from_millis(200))),
+    ///         Err(MassMoveError::LockError { .. })
+    ///     ));
+    ///     drop(lock);
+    ///     assert!(Lock::acquire(&path, None).is_ok());
+    ///     Ok(())
+    /// }
+    /// ```
+    /// # Behavior
+    /// The lock file is never removed, since another process may be waiting on it. Waiting stops early on Ctrl-C.
+    pub fn acquire(path: &Path, wait: Option<Duration>) -> Result<Lock, MassMoveError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        // A wait too long to be represented waits without a deadline.
+        let deadline = Instant::now().checked_add(wait.unwrap_or_default());
+        let mut waiting = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Lock { _file: file }),
+                Err(TryLockError::Error(error)) => return Err(error.into()),
+                Err(TryLockError::WouldBlock) => {}
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) || sys::interrupted() {
+                return Err(MassMoveError::LockError {
+                    path: path.to_path_buf(),
+                });
+            }
+            if !waiting {
+                eprintln!(
+                    "Waiting for the lock {} held by another run",
+                    path.display()
+                );
+                waiting = true;
+            }
+            let left = deadline.map_or(RETRY_INTERVAL, |deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            thread::sleep(RETRY_INTERVAL.min(left));
+        }
+    }
+
+    /// Returns the default lock file of a batch: `.mmv-lock` in the deepest directory containing both the fixed
+    /// directory of `source_pattern` (without wildcards) and of `destination_pattern` (without `#` markers).
+    /// # Example
+    /// ```rust
+    /// use mmv::lock::Lock;
+    /// use std::path::PathBuf;
+    ///
+    /// let path = Lock::path_for("/srv/inbox/new/*.csv", "/srv/inbox/done/#1/data.csv");
+    /// assert_eq!(path, PathBuf::from("/srv/inbox/.mmv-lock"));
+    /// ```
+    pub fn path_for(source_pattern: &str, destination_pattern: &str) -> PathBuf {
+        let source_dir = fixed_dir(source_pattern, &['*', '?', '[']);
+        let destination_dir = fixed_dir(destination_pattern, &['#']);
+        let common: PathBuf = source_dir
+            .components()
+            .zip(destination_dir.components())
+            .take_while(|(left, right)| left == right)
+            .map(|(component, _)| component)
+            .collect();
+        common.join(LOCK_NAME)
+    }
+}
+
+/// Returns the absolute directory of `pattern` up to the first component containing one of `markers`.
+fn fixed_dir(pattern: &str, markers: &[char]) -> PathBuf {
+    let directory: PathBuf = Path::new(pattern)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .components()
+        .take_while(|component| {
+            !matches!(component, Component::Normal(name) if name.to_string_lossy().contains(markers))
+        })
+        .collect();
+    if directory.as_os_str().is_empty() {
+        return normalize(&absolute(Path::new(".")));
+    }
+    normalize(&absolute(&directory))
+}
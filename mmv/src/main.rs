@@ -1,15 +1,42 @@
 pub mod args;
+pub mod conflict;
+pub mod copy;
 pub mod errors;
 pub mod fs_utils;
+pub mod interactive;
+pub mod journal;
+pub mod links;
+pub mod lock;
 pub mod mmv;
+pub mod open_files;
+pub mod origin;
+pub mod refs;
+pub mod special;
+pub mod sys;
+pub mod trash;
+
+use std::process;
 
 use crate::args::clap::Parser;
-use crate::args::CLI;
-use crate::mmv::mmv;
+use crate::args::{Command, CLI};
+use crate::errors::MassMoveError;
+use crate::mmv::{mmv, restore};
 
 fn main() {
-    let args = CLI::parse();
-    if let Err(error) = mmv(args) {
-        println!("{:?}", error);
+    let mut args = CLI::parse();
+    sys::catch_interrupts();
+    let result = match args.command.take() {
+        Some(Command::Restore { pattern, force }) => restore(&pattern, force),
+        None => mmv(args),
+    };
+    if let Err(error) = result {
+        // The batch summary has already been printed by `mmv`.
+        if !matches!(
+            error,
+            MassMoveError::BatchError(_) | MassMoveError::InterruptedError(_)
+        ) {
+            println!("{:?}", error);
+        }
+        process::exit(error.exit_code());
     }
 }
@@ -1,8 +1,33 @@
-use std::path::PathBuf;
+use std::io::{BufRead, Write};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{self, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
 
 use crate::args::CLI;
+use crate::conflict::{ConflictPolicy, ConflictPrompt};
+use crate::copy::{CopyStrategy, Progress};
 use crate::errors::MassMoveError;
-use crate::fs_utils::{capture_files_by_pattern, move_file, rename_file_by_pattern};
+use crate::fs_utils::{
+    capture_files_by_pattern, follows_links, inode, is_mmv_file, move_file, natural_cmp,
+    rename_file_by_pattern, HardLinks, MoveOptions, MoveOutcome, Operation,
+};
+use crate::interactive::{Decision, Interactive};
+use crate::journal::{is_finished_copy, Journal};
+use crate::links::{fix_links, is_symlink};
+use crate::lock::Lock;
+use crate::open_files::{OpenFilePolicy, OpenFiles};
+use crate::origin::{read_origin, remove_origin, tag_origin};
+use crate::refs::{apply_reference_update, find_reference_updates, reference_diff};
+use crate::special::{file_metadata, special_kind, SpecialPolicy};
+use crate::sys;
 
 /// Moves and renames multiple files based on the provided source and destination patterns.
 /// # Arguments
@@ -10,11 +35,39 @@ use crate::fs_utils::{capture_files_by_pattern, move_file, rename_file_by_patter
 ///     - `source_pattern` - A pattern to capture files from the source directory, e.g., `"path/to/files_*.txt"`.
 ///     - `destination_pattern` - A pattern to rename and move the captured files to the destination directory, e.g., `"new_path/to/renamed_#1.txt"`.
 ///     - `force` - A boolean indicating whether to overwrite files if they already exist in the destination.
+///     - `mode` - The operation used to transfer files: move, copy, hard link, symbolic link, reflink, append or exchange.
+///     - `append` - A boolean selecting the append operation, same as `mode` set to append.
+///     - `exchange` - A boolean selecting the atomic exchange operation, same as `mode` set to exchange.
+///     - `on_conflict` - The policy used if a destination file exists and `force` is not set.
+///     - `backup_dir` - An optional directory where overwritten destination files are kept.
+///     - `trash` - Whether overwritten destination files are moved into the trash of the user instead.
+///     - `progress` - A boolean indicating whether to show the progress of copies to another file system.
+///     - `verify` - A boolean indicating whether to verify copies to another file system before deleting the sources.
+///     - `symlinks` - What happens to source files that are symbolic links: preserve, follow or skip.
+///     - `rewrite_links` - A boolean indicating whether relative targets of moved symbolic links are rewritten.
+///     - `fix_links` - An optional directory tree whose symbolic links to moved files are retargeted.
+///     - `update_refs` - An optional glob pattern of text files whose references to moved files are rewritten.
+///     - `dry_run` - A boolean indicating whether to only print the planned moves and reference changes.
+///     - `journal` - A boolean indicating whether to record every moved file in the journal, not only copies to another file system.
+///     - `resume` - A boolean indicating whether to finish a batch that was interrupted before.
+///     - `jobs` - The number of files moved concurrently.
+///     - `keep_going` - A boolean indicating whether to attempt every file even if some of them fail.
+///     - `interactive` - An optional mode of asking the user for a confirmation before renaming.
+///     - `skip_open` - An optional policy for files that are open in another process: skip them or fail the batch.
+///     - `lock` - An optional lock file, or the default `.mmv-lock`, locked for the whole batch.
+///     - `wait` - An optional number of seconds to wait for the lock.
+///     - `tag_origin` - A boolean indicating whether to record the original path of every moved file, see `restore`.
+///
 /// The function:
 /// 1. Finds all files matching the `source_pattern`.
 /// 2. Renames each captured file according to the `destination_pattern`.
 /// 3. Moves each file to the destination directory.
-/// 4. Prints the source and destination paths for each file moved.
+/// 4. Prints the source and destination paths for each file moved, and the strategy of copies to another file system.
+/// 5. In `keep_going` mode, prints a summary of moved and failed files.
+/// 6. Once a file is copied to another file system, or from the start with `journal`, records every moved file in the
+///    journal of the batch, and removes the journal once all files are moved.
+/// 7. With `fix_links`, retargets the symbolic links under the given directory that pointed at the moved files.
+/// 8. With `update_refs`, rewrites the references to the moved files in the matching text files and reports the changes.
 /// # Example
 /// ```rust
 /// use tempfile::TempDir;
@@ -29,11 +82,11 @@ use crate::fs_utils::{capture_files_by_pattern, move_file, rename_file_by_patter
 ///
 ///     let mut source = File::create(&source_file).unwrap();
 ///     writeln!(source, "This is a test file.").unwrap();
-
 ///     let args = CLI {
 ///         source_pattern: format!("{}/some_*_filename.txt", dir.path().display()),
 ///         destination_pattern: format!("{}/changed_#1_filename.txt", dir.path().display()),
 ///         force: false,
+///         ..Default::default()
 ///     };
 ///
 ///     let result = mmv(args);
@@ -45,26 +98,711 @@ use crate::fs_utils::{capture_files_by_pattern, move_file, rename_file_by_patter
 /// # Errors
 /// - Returns `MassMoveError::NotFoundError` if no files match the `source_pattern`.
 /// - Returns `MassMoveError::MatchError` if a file does not match the renaming pattern.
-/// - Returns `MassMoveError::FileExistsError` if a destination file exists and the `force` flag is `false`.
+/// - Returns `MassMoveError::FileExistsError` if a destination file exists, the `force` flag is `false` and the `on_conflict` policy is `error`.
 /// - Returns `MassMoveError::IoError` for any I/O issues during file operations (moving, renaming, etc.).
+/// - Returns `MassMoveError::JournalError` if the journal of an interrupted batch with the same patterns exists and
+///   `resume` is not set, or if the journal is damaged.
+/// - Returns `MassMoveError::InterruptedError` if the process received Ctrl-C, see `sys::catch_interrupts`.
+/// - Returns `MassMoveError::BatchError` if `keep_going` is set and at least one file could not be moved.
 /// # Behavior
-/// - The function will stop and return the first error encountered (e.g., if one of the files cannot be renamed or moved).
-/// - If `force` is set to `true`, existing files in the destination directory will be overwritten.
-
+/// - The function will stop and return the first error encountered (e.g., if one of the files cannot be renamed or moved), unless `keep_going` is set.
+/// - If `force` is set to `true`, existing files in the destination directory will be overwritten. Otherwise the `on_conflict` policy decides.
+/// - Files are processed in natural order of their source paths, so appended parts are merged in a deterministic order (`part2` before `part10`).
+/// - With `dry_run`, only the planned moves and the changes `update_refs` would make are printed, as a diff. Conflicts
+///   with existing files are not checked.
+/// - With `lock`, an exclusive `flock` on the lock file is taken before the files are captured and held until the batch
+///   ends, so concurrent runs over the same tree do not interfere. Without `wait` the batch fails at once if the lock is held.
+/// - With `skip_open`, the file descriptors of all processes are scanned once before the batch. Files that are open in
+///   another process are skipped with a warning, or with `OpenFilePolicy::Fail` no file is moved if any of them is open.
+/// - With `tag_origin`, the original path of every moved file is recorded in its `user.mmv.origin` extended attribute,
+///   so `restore` can move it back later. Symbolic links are not tagged.
+/// - Named pipes, sockets and device nodes are skipped with a warning unless `special` is `SpecialPolicy::Recreate`.
+/// - Hard links to the same file among the captured files stay hard links to the same file when they are moved to another
+///   file system: the file is copied once and its other links are linked to the copy.
+/// - With `resume`, the files the journal records as done are skipped. A source is deleted without moving it again only
+///   if the journal records a complete copy of it and that copy has the same content.
+/// - On Ctrl-C the current file is finished, or rolled back if it is being copied to another file system. The remaining files
+///   are left in place, a summary is printed and the journal is kept, so the batch can be finished with `resume`.
+/// - With `jobs` above 1, files are split into groups that share no source or destination path, e.g. files appended to the same
+///   destination or a chain like `a -> b`, `b -> c`. Each group is moved in order while the groups are moved concurrently.
+///   The moved files, warnings and progress lines are still printed in the order of their source paths. With `interactive` or the `ask` conflict policy
+///   the files are always moved one by one.
+/// - If `interactive` is set, every rename (or only every overwrite) has to be confirmed on stdin. A confirmed overwrite does not need `force`.
 pub fn mmv(args: CLI) -> Result<(), MassMoveError> {
-    let captured_files = capture_files_by_pattern(&args.source_pattern)?;
+    let _lock = acquire_lock(&args)?;
+    let mut captured_files: Vec<String> = capture_files_by_pattern(&args.source_pattern)?
+        .into_iter()
+        .filter(|file| !is_mmv_file(Path::new(file)))
+        .collect();
+    captured_files.sort_by(|left, right| natural_cmp(left, right));
+
+    if args.dry_run {
+        return dry_run(&args, &captured_files);
+    }
+
+    let mut moved = 0;
+    let mut failures: Vec<(String, MassMoveError)> = Vec::new();
+    let mut interactive = args.interactive.map(Interactive::stdio);
+    // One prompt for the whole batch, so `[a]ll` also applies to the following conflicts.
+    let prompt = (args.on_conflict == ConflictPolicy::Ask).then(ConflictPrompt::stdio);
+    let open_files = match args.skip_open {
+        Some(policy) => Some(scan_open_files(policy, &captured_files)?),
+        None => None,
+    };
+    let journal = open_journal(&args)?.map(|journal| Arc::new(Mutex::new(journal)));
+    let mut quit = false;
+    let mut remaining = 0;
+    let mut first_error = None;
+    let track_moves =
+        (args.fix_links.is_some() || args.update_refs.is_some()) && leaves_old_paths(&args);
+    let mut moved_files: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let hard_links = HardLinks::default();
+
+    let handle = |source_file: &str, result: Option<Result<Step, MassMoveError>>| {
+        if let Some(Ok(Step::Moved(renamed_file, _))) = &result {
+            if track_moves {
+                moved_files.push((PathBuf::from(source_file), PathBuf::from(renamed_file)));
+            }
+        }
+        match result {
+            Some(Ok(Step::Moved(renamed_file, None))) => {
+                moved += 1;
+                println!("{} -> {}", source_file, renamed_file);
+            }
+            Some(Ok(Step::Moved(renamed_file, Some(strategy)))) => {
+                moved += 1;
+                println!(
+                    "{} -> {} (copied with {})",
+                    source_file, renamed_file, strategy
+                );
+            }
+            Some(Ok(Step::Exchanged(renamed_file))) => {
+                moved += 1;
+                println!("{} <-> {}", source_file, renamed_file);
+            }
+            Some(Ok(Step::Skipped)) => {}
+            Some(Ok(Step::Quit)) => {
+                quit = true;
+                return false;
+            }
+            // The file was not started, or it was rolled back on Ctrl-C, so it is left in place.
+            None => remaining += 1,
+            Some(Err(_)) if sys::interrupted() => remaining += 1,
+            Some(Err(error)) if args.keep_going => failures.push((source_file.to_string(), error)),
+            Some(Err(error)) => {
+                first_error = Some(error);
+                return false;
+            }
+        }
+        true
+    };
+
+    let batch = Batch {
+        journal: journal.as_ref(),
+        open_files: open_files.as_ref(),
+        hard_links: &hard_links,
+        prompt: prompt.as_ref(),
+    };
+    // Prompts cannot be answered concurrently.
+    let jobs = if args.interactive.is_some() || args.on_conflict == ConflictPolicy::Ask {
+        1
+    } else {
+        args.jobs.max(1)
+    };
+    if jobs == 1 {
+        run_sequential(
+            &captured_files,
+            |source_file| {
+                move_by_pattern(
+                    &args,
+                    source_file,
+                    interactive.as_mut(),
+                    batch,
+                    &Log::default(),
+                )
+            },
+            handle,
+        );
+    } else {
+        run_parallel(
+            &captured_files,
+            independent_groups(&args, &captured_files),
+            jobs,
+            |source_file, log| {
+                move_by_pattern(
+                    &args,
+                    source_file,
+                    None::<&mut Interactive<io::Empty, io::Sink>>,
+                    batch,
+                    log,
+                )
+            },
+            handle,
+        );
+    }
+
+    let fixed_links = match &args.fix_links {
+        Some(root) => fix_links(root, &moved_files).map(|fixed| {
+            for fixed_link in fixed {
+                println!(
+                    "Fixed link {}: {} -> {}",
+                    fixed_link.link.display(),
+                    fixed_link.old_target.display(),
+                    fixed_link.new_target.display()
+                );
+            }
+        }),
+        None => Ok(()),
+    };
+    let updated_refs = match &args.update_refs {
+        Some(pattern) => update_references(pattern, &moved_files),
+        None => Ok(()),
+    };
+
+    if sys::interrupted() && remaining > 0 {
+        print_summary(moved, &failures);
+        println!("Interrupted, {} files left in place", remaining);
+        return Err(MassMoveError::InterruptedError(remaining));
+    }
+
+    if let Some(error) = first_error {
+        return Err(error);
+    }
+
+    if args.keep_going {
+        print_summary(moved, &failures);
+    }
+
+    if !failures.is_empty() {
+        return Err(MassMoveError::BatchError(failures));
+    }
+
+    fixed_links?;
+    updated_refs?;
+    if let Some(journal) = journal.filter(|_| !quit).and_then(Arc::into_inner) {
+        journal.into_inner().unwrap().finish()?;
+    }
+    Ok(())
+}
+
+/// Moves the files tagged by `--tag-origin` back to the paths they were moved from.
+/// # Arguments
+/// - `pattern` - a pattern of the moved files, e.g. `"new_path/to/renamed_*.txt"`.
+/// - `force` - a boolean indicating whether to overwrite files that exist at the original paths.
+/// # Errors
+/// - Returns `MassMoveError::NotFoundError` if no files match the `pattern`.
+/// - Returns `MassMoveError::FileExistsError` if a file exists at an original path and `force` is `false`.
+/// - Returns `MassMoveError::IoError` if an origin cannot be read or the file cannot be moved.
+/// - Returns `MassMoveError::InterruptedError` if the process received Ctrl-C, see `sys::catch_interrupts`.
+/// # Behavior
+/// - The files are restored in natural order, and the function stops at the first error.
+/// - Files without a recorded origin are left in place with a warning. Missing directories of the original paths are
+///   created, and the origin is removed from every restored file.
+pub fn restore(pattern: &str, force: bool) -> Result<(), MassMoveError> {
+    let mut files: Vec<String> = capture_files_by_pattern(pattern)?.into_iter().collect();
+    files.sort_by(|left, right| natural_cmp(left, right));
+    let options = MoveOptions {
+        policy: if force {
+            ConflictPolicy::Overwrite
+        } else {
+            ConflictPolicy::Error
+        },
+        ..Default::default()
+    };
+
+    let interrupted = |remaining: usize| {
+        println!("Interrupted, {} files left in place", remaining);
+        Err(MassMoveError::InterruptedError(remaining))
+    };
+    for (index, file) in files.iter().enumerate() {
+        if sys::interrupted() {
+            return interrupted(files.len() - index);
+        }
+        let Some(origin) = read_origin(Path::new(file))? else {
+            eprintln!("Warning: {} has no recorded origin", file);
+            continue;
+        };
+        if let Some(parent) = origin.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // A copy to another file system interrupted by Ctrl-C is rolled back, so the file is left in place.
+        let outcome = match move_file(Path::new(file), &origin.path, &options) {
+            Err(_) if sys::interrupted() => return interrupted(files.len() - index),
+            outcome => outcome?,
+        };
+        if let MoveOutcome::Moved { destination, .. } = outcome {
+            remove_origin(&destination)?;
+            println!("{} -> {}", file, destination.display());
+        }
+    }
+    Ok(())
+}
+
+/// Moves the files one by one, passing every result to `handle` until it returns `false`.
+fn run_sequential(
+    files: &[String],
+    mut attempt: impl FnMut(&str) -> Result<Step, MassMoveError>,
+    mut handle: impl FnMut(&str, Option<Result<Step, MassMoveError>>) -> bool,
+) {
+    for source_file in files {
+        let result = if sys::interrupted() {
+            None
+        } else {
+            Some(attempt(source_file))
+        };
+        if !handle(source_file, result) {
+            break;
+        }
+    }
+}
+
+/// Moves the groups of files on `jobs` threads. The files of a group are moved one by one in their order.
+/// The results are passed to `handle` in the order of `files`, a file that was not started is passed as `None`.
+/// The warnings and progress lines of every file are written to its own `Log` and printed right before its result is
+/// handled, so they appear in the same order as in a sequential run.
+/// Once `handle` returns `false`, no more files are started, and a failure of a file that was already being moved is
+/// passed as `None` (the file is left in place), so the first failure in the order of `files` is the one reported.
+fn run_parallel(
+    files: &[String],
+    groups: Vec<Vec<usize>>,
+    jobs: usize,
+    attempt: impl Fn(&str, &Log) -> Result<Step, MassMoveError> + Sync,
+    mut handle: impl FnMut(&str, Option<Result<Step, MassMoveError>>) -> bool,
+) {
+    let next_group = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(groups.len()) {
+            let sender = sender.clone();
+            let (groups, next_group, stop, attempt) = (&groups, &next_group, &stop, &attempt);
+            scope.spawn(move || {
+                while let Some(group) = groups.get(next_group.fetch_add(1, Ordering::SeqCst)) {
+                    for &index in group {
+                        let log = Log::buffered();
+                        let result = if stop.load(Ordering::SeqCst) || sys::interrupted() {
+                            None
+                        } else {
+                            Some(attempt(&files[index], &log))
+                        };
+                        if sender.send((index, (log.take(), result))).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some((output, mut result)) = pending.remove(&next) {
+                // A failure after the batch stopped is not reported, a sequential run would not have started the file.
+                if stop.load(Ordering::SeqCst) && matches!(result, Some(Err(_))) {
+                    result = None;
+                } else {
+                    eprint!("{}", output);
+                }
+                if !handle(&files[next], result) {
+                    stop.store(true, Ordering::SeqCst);
+                }
+                next += 1;
+            }
+        }
+    });
+}
+
+/// Splits the files into groups that can be moved independently of each other. Two files belong to the same group if
+/// they have the same destination, if the destination of one is the source of the other (a chain like `a -> b`, `b -> c`),
+/// or if they are hard links to the same file.
+/// Every group keeps the order of `files`, and the groups are ordered by their first file.
+fn independent_groups(args: &CLI, files: &[String]) -> Vec<Vec<usize>> {
+    let mut parents: Vec<usize> = (0..files.len()).collect();
+    fn find(parents: &mut [usize], index: usize) -> usize {
+        let mut root = index;
+        while parents[root] != root {
+            root = parents[root];
+        }
+        parents[index] = root;
+        root
+    }
+
+    let key = |file: &str| path::absolute(file).unwrap_or_else(|_| PathBuf::from(file));
+    let mut owners: HashMap<PathBuf, usize> = HashMap::new();
+    let mut inode_owners: HashMap<(u64, u64), usize> = HashMap::new();
+    for (index, source_file) in files.iter().enumerate() {
+        let destination =
+            rename_file_by_pattern(&args.source_pattern, source_file, &args.destination_pattern)
+                .ok();
+        let mut related: Vec<usize> = std::iter::once(source_file)
+            .chain(destination.as_ref())
+            .map(|path| *owners.entry(key(path)).or_insert(index))
+            .collect();
+        // Hard links to the same file are moved in order, so the first one is copied and the others are linked to it.
+        if let Some(inode) = fs::symlink_metadata(source_file)
+            .ok()
+            .and_then(|metadata| inode(&metadata))
+        {
+            related.push(*inode_owners.entry(inode).or_insert(index));
+        }
+        for owner in related {
+            let (root, owner_root) = (find(&mut parents, index), find(&mut parents, owner));
+            parents[root.max(owner_root)] = root.min(owner_root);
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_root: HashMap<usize, usize> = HashMap::new();
+    for index in 0..files.len() {
+        let root = find(&mut parents, index);
+        let group = *group_of_root.entry(root).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(index);
+    }
+    groups
+}
+
+/// Returns whether the operation removes the source files, so references to their old paths break.
+fn leaves_old_paths(args: &CLI) -> bool {
+    matches!(args.operation(), Operation::Move | Operation::Exchange)
+}
 
+/// Prints the planned moves and the changes `update_refs` would make as a diff, without changing any file.
+fn dry_run(args: &CLI, captured_files: &[String]) -> Result<(), MassMoveError> {
+    let mut planned_files = Vec::new();
     for source_file in captured_files {
-        let renamed_file = rename_file_by_pattern(
-            &args.source_pattern,
-            &source_file,
-            &args.destination_pattern,
-        )?;
-        let source_path = PathBuf::from(&source_file);
-        let destination_path = PathBuf::from(&renamed_file);
-        move_file(&source_path, &destination_path, &args.force)?;
+        let renamed_file =
+            rename_file_by_pattern(&args.source_pattern, source_file, &args.destination_pattern)?;
         println!("{} -> {}", source_file, renamed_file);
+        planned_files.push((PathBuf::from(source_file), PathBuf::from(renamed_file)));
     }
 
+    if let Some(pattern) = args.update_refs.as_ref().filter(|_| leaves_old_paths(args)) {
+        for update in find_reference_updates(pattern, &planned_files)? {
+            print!("{}", reference_diff(&update));
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the references to the moved files in the text files matching `pattern` and reports every updated file.
+fn update_references(
+    pattern: &str,
+    moved_files: &[(PathBuf, PathBuf)],
+) -> Result<(), MassMoveError> {
+    let updates = find_reference_updates(pattern, moved_files)?;
+    let mut replacements = 0;
+    for update in &updates {
+        apply_reference_update(update)?;
+        replacements += update.replacements;
+        println!(
+            "Updated {} references in {}",
+            update.replacements,
+            update.file.display()
+        );
+    }
+    println!(
+        "References updated: {} in {} files",
+        replacements,
+        updates.len()
+    );
     Ok(())
 }
+
+/// Scans the files open in other processes. With `OpenFilePolicy::Fail`, returns an error for the first captured file that is open.
+fn scan_open_files(
+    policy: OpenFilePolicy,
+    captured_files: &[String],
+) -> Result<OpenFiles, MassMoveError> {
+    let open_files = OpenFiles::scan()?;
+    if policy == OpenFilePolicy::Fail {
+        for source_file in captured_files {
+            let processes = open_files.processes(Path::new(source_file));
+            if !processes.is_empty() {
+                return Err(MassMoveError::FileInUseError {
+                    path: PathBuf::from(source_file),
+                    processes: processes.to_vec(),
+                });
+            }
+        }
+    }
+    Ok(open_files)
+}
+
+/// Takes the lock of the batch if `--lock` is given.
+fn acquire_lock(args: &CLI) -> Result<Option<Lock>, MassMoveError> {
+    let Some(path) = &args.lock else {
+        return Ok(None);
+    };
+    let path = path
+        .clone()
+        .unwrap_or_else(|| Lock::path_for(&args.source_pattern, &args.destination_pattern));
+    Lock::acquire(&path, args.wait.map(Duration::from_secs)).map(Some)
+}
+
+/// Opens the journal of the batch, which is started at once with `--journal`, or returns `None` if its directory does
+/// not exist yet.
+fn open_journal(args: &CLI) -> Result<Option<Journal>, MassMoveError> {
+    let path = Journal::path_for(&args.source_pattern, &args.destination_pattern);
+    let journal = Journal::open(
+        &path,
+        &args.source_pattern,
+        &args.destination_pattern,
+        args.resume,
+    )
+    .and_then(|mut journal| {
+        if args.journal {
+            journal.start()?;
+        }
+        Ok(journal)
+    });
+    match journal {
+        Ok(journal) => Ok(Some(journal)),
+        Err(MassMoveError::StdIOError(error)) if error.kind() == io::ErrorKind::NotFound => {
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// The state shared by all files of a batch.
+#[derive(Debug, Clone, Copy)]
+struct Batch<'a> {
+    journal: Option<&'a Arc<Mutex<Journal>>>,
+    open_files: Option<&'a OpenFiles>,
+    hard_links: &'a HardLinks,
+    /// The prompt of `ConflictPolicy::Ask`, shared so `[a]ll` also applies to the following conflicts.
+    prompt: Option<&'a ConflictPrompt>,
+}
+
+/// Where the warnings and progress lines of a file go: straight to stderr, or into a buffer that `run_parallel` prints
+/// once the result of the file is handled.
+#[derive(Debug, Clone, Default)]
+struct Log(Option<Arc<Mutex<String>>>);
+
+impl Log {
+    fn buffered() -> Self {
+        Log(Some(Arc::default()))
+    }
+
+    fn print(&self, text: &str) {
+        match &self.0 {
+            Some(buffer) => buffer.lock().unwrap().push_str(text),
+            None => eprint!("{}", text),
+        }
+    }
+
+    /// Returns the buffered text, which is empty if the log prints to stderr.
+    fn take(&self) -> String {
+        self.0
+            .as_ref()
+            .map(|buffer| std::mem::take(&mut *buffer.lock().unwrap()))
+            .unwrap_or_default()
+    }
+}
+
+/// What happened to a single captured file.
+enum Step {
+    Moved(String, Option<CopyStrategy>),
+    Exchanged(String),
+    Skipped,
+    Quit,
+}
+
+fn move_by_pattern(
+    args: &CLI,
+    source_file: &str,
+    interactive: Option<&mut Interactive<impl BufRead, impl Write>>,
+    batch: Batch,
+    log: &Log,
+) -> Result<Step, MassMoveError> {
+    let Batch {
+        journal,
+        open_files,
+        hard_links,
+        prompt,
+    } = batch;
+    let mut renamed_file =
+        rename_file_by_pattern(&args.source_pattern, source_file, &args.destination_pattern)?;
+    // The journal keys files by the destination computed from the pattern, before it is edited or renamed on a conflict.
+    let planned_file = renamed_file.clone();
+
+    if args.resume {
+        if journal
+            .as_ref()
+            .is_some_and(|journal| journal.lock().unwrap().is_done(source_file, &planned_file))
+        {
+            return Ok(Step::Skipped);
+        }
+        // The interrupted batch left a partial copy behind.
+        let temporary = journal.and_then(|journal| {
+            let journal = journal.lock().unwrap();
+            journal.temporary_of(source_file).map(PathBuf::from)
+        });
+        if let Some(temporary) = temporary.filter(|temporary| is_mmv_file(temporary)) {
+            match fs::remove_file(&temporary) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+                _ => {}
+            }
+        }
+        // The copy was renamed into place, but the interrupted batch did not delete the source yet.
+        let copy = journal.and_then(|journal| {
+            let journal = journal.lock().unwrap();
+            journal.copied_to(source_file).map(str::to_string)
+        });
+        if let Some(copy) = copy.filter(|_| args.operation() == Operation::Move) {
+            if is_finished_copy(Path::new(source_file), Path::new(&copy))? {
+                fs::remove_file(source_file)?;
+                if let Some(journal) = journal {
+                    journal.lock().unwrap().record(source_file, &planned_file)?;
+                }
+                return Ok(Step::Moved(copy, None));
+            }
+        }
+    }
+    if args.special == SpecialPolicy::Skip {
+        let follow = follows_links(args.operation(), args.symlinks);
+        if let Some(kind) = file_metadata(Path::new(source_file), follow)
+            .ok()
+            .and_then(|metadata| special_kind(&metadata))
+        {
+            log.print(&format!(
+                "Warning: skipped {}, which is a {}\n",
+                source_file, kind
+            ));
+            return Ok(Step::Skipped);
+        }
+    }
+    if let Some(processes) = open_files
+        .map(|open_files| open_files.processes(Path::new(source_file)))
+        .filter(|processes| !processes.is_empty())
+    {
+        log.print(&format!(
+            "Warning: skipped {}, which is open by {}\n",
+            source_file,
+            processes.join(", ")
+        ));
+        return Ok(Step::Skipped);
+    }
+    let mut policy = if args.force {
+        ConflictPolicy::Overwrite
+    } else {
+        args.on_conflict
+    };
+
+    if let Some(interactive) = interactive {
+        let decision = interactive.confirm(source_file, &renamed_file)?;
+        // Whatever was answered, Ctrl-C at the prompt leaves the file in place.
+        if sys::interrupted() {
+            return Err(interrupted_error());
+        }
+        match decision {
+            Decision::Move {
+                destination,
+                overwrite,
+            } => {
+                renamed_file = destination;
+                if overwrite {
+                    policy = ConflictPolicy::Overwrite;
+                }
+            }
+            Decision::Skip => return Ok(Step::Skipped),
+            Decision::Quit => return Ok(Step::Quit),
+        }
+    }
+
+    let source_path = PathBuf::from(source_file);
+    let destination_path = PathBuf::from(&renamed_file);
+    let options = MoveOptions {
+        operation: args.operation(),
+        policy,
+        prompt: prompt.cloned(),
+        backup_dir: args.backup_dir.clone(),
+        trash: args.trash,
+        progress: args.progress.then(|| {
+            let log = log.clone();
+            Progress::new(move |source, copied, total| print_progress(&log, source, copied, total))
+        }),
+        verify: args.verify,
+        symlinks: args.symlinks,
+        rewrite_links: args.rewrite_links,
+        special: args.special,
+        hard_links: Some(hard_links.clone()),
+        journal: journal.cloned(),
+    };
+    let outcome = move_file(&source_path, &destination_path, &options)?;
+    if let MoveOutcome::Moved { destination, .. } = &outcome {
+        // Symbolic links cannot carry user extended attributes.
+        if args.tag_origin && args.operation() == Operation::Move && !is_symlink(destination) {
+            if let Err(error) = tag_origin(destination, &source_path) {
+                log.print(&format!(
+                    "Warning: could not record the origin of {}: {}\n",
+                    destination.display(),
+                    error
+                ));
+            }
+        }
+    }
+    if let (Some(journal), MoveOutcome::Moved { .. } | MoveOutcome::Exchanged { .. }) =
+        (journal, &outcome)
+    {
+        journal.lock().unwrap().record(source_file, &planned_file)?;
+    }
+    match outcome {
+        MoveOutcome::Moved {
+            destination,
+            unpreserved,
+            strategy,
+        } => {
+            if !unpreserved.is_empty() {
+                log.print(&format!(
+                    "Warning: could not preserve {} of {}\n",
+                    unpreserved.join(", "),
+                    destination.display()
+                ));
+            }
+            Ok(Step::Moved(destination.display().to_string(), strategy))
+        }
+        MoveOutcome::Exchanged { destination } => {
+            Ok(Step::Exchanged(destination.display().to_string()))
+        }
+        MoveOutcome::Skipped => Ok(Step::Skipped),
+        MoveOutcome::Quit if sys::interrupted() => Err(interrupted_error()),
+        MoveOutcome::Quit => Ok(Step::Quit),
+    }
+}
+
+/// The error of a file left in place because of Ctrl-C, which `mmv` counts among the remaining files.
+fn interrupted_error() -> MassMoveError {
+    io::Error::new(
+        io::ErrorKind::Interrupted,
+        "The batch was interrupted by the user.",
+    )
+    .into()
+}
+
+fn print_progress(log: &Log, source: &Path, copied: u64, total: u64) {
+    let percent = (copied * 100).checked_div(total).unwrap_or(100);
+    log.print(&format!(
+        "\r{}: {} of {} bytes ({}%)",
+        source.display(),
+        copied,
+        total,
+        percent
+    ));
+    if copied >= total {
+        log.print("\n");
+    }
+}
+
+fn print_summary(moved: usize, failures: &[(String, MassMoveError)]) {
+    println!("Moved: {}, failed: {}", moved, failures.len());
+    for (source_file, error) in failures {
+        println!("  {}: {:?}", source_file, error);
+    }
+}
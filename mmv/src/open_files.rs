@@ -0,0 +1,90 @@
+use crate::fs_utils::inode;
+use clap::ValueEnum;
+use std::{collections::HashMap, fs, io, path::Path, process};
+
+/// What happens to captured files that are open in another process.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OpenFilePolicy {
+    /// Leave open files in place with a warning and move the others.
+    #[default]
+    Skip,
+    /// Move no file at all if any of them is open.
+    Fail,
+}
+
+/// The files open in other processes, by device and inode, with the processes holding them, e.g. `1234 (rsyslogd)`.
+#[derive(Debug, Default)]
+pub struct OpenFiles(HashMap<(u64, u64), Vec<String>>);
+
+impl OpenFiles {
+    /// This function scans the file descriptors of all processes in `/proc/*/fd`, except the current one.
+    /// # Return value
+    /// Returns `io::Result<OpenFiles>`, where:
+    /// - If succesful - returns the files referenced by any descriptor.
+    /// - In case of an error, the OS error is returned if `/proc` could not be read, e.g. on platforms other than Linux.
+    /// # Example
+    /// ```rust
+    /// use mmv::open_files::OpenFiles;
+    /// use std::{fs::File, process::Command};
+    /// use tempfile::TempDir;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let dir = TempDir::new()?;
+    ///     let log = dir.path().join("app.log");
+    ///     File::create(&log)?;
+    ///     let mut daemon = Command::new("sleep").arg("10").stdin(File::open(&log)?).spawn()?;
+    ///
+    ///     let open_files = OpenFiles::scan()?;
+    ///     assert_eq!(open_files.processes(&log).len(), 1);
+    ///     daemon.kill()?;
+    ///     daemon.wait()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    /// # Behavior
+    /// - Processes whose descriptors the user is not allowed to inspect, e.g. those of other users, are ignored.
+    /// - The scan is a snapshot: files opened after it are not detected.
+    pub fn scan() -> io::Result<OpenFiles> {
+        let own_id = process::id().to_string();
+        let mut files: HashMap<(u64, u64), Vec<String>> = HashMap::new();
+        for entry in fs::read_dir("/proc")? {
+            let process_dir = entry?.path();
+            let id = process_dir
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            if id == own_id || id.parse::<u32>().is_err() {
+                continue;
+            }
+            // The process may exit or hide its descriptors at any time.
+            let Ok(descriptors) = fs::read_dir(process_dir.join("fd")) else {
+                continue;
+            };
+            let name = fs::read_to_string(process_dir.join("comm")).unwrap_or_default();
+            let process = format!("{} ({})", id, name.trim_end());
+            for descriptor in descriptors.flatten() {
+                let Some(file) = fs::metadata(descriptor.path())
+                    .ok()
+                    .and_then(|metadata| inode(&metadata))
+                else {
+                    continue;
+                };
+                let processes = files.entry(file).or_default();
+                if !processes.contains(&process) {
+                    processes.push(process.clone());
+                }
+            }
+        }
+        Ok(OpenFiles(files))
+    }
+
+    /// Returns the processes that have `path` open, or an empty slice. A symbolic link is never open itself.
+    pub fn processes(&self, path: &Path) -> &[String] {
+        fs::symlink_metadata(path)
+            .ok()
+            .and_then(|metadata| inode(&metadata))
+            .and_then(|file| self.0.get(&file))
+            .map_or(&[], Vec::as_slice)
+    }
+}
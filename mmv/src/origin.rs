@@ -0,0 +1,98 @@
+use crate::conflict::timestamp;
+use crate::links::{absolute, normalize};
+use crate::sys;
+use std::{
+    ffi::OsStr,
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Name of the extended attribute holding the path a file was moved from.
+pub const ORIGIN_XATTR: &str = "user.mmv.origin";
+
+/// The path a moved file was moved from, as recorded by `tag_origin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The absolute path of the file before it was moved.
+    pub path: PathBuf,
+    /// When the file was moved, as a UTC timestamp, e.g. `20240101T120000Z`.
+    pub timestamp: String,
+}
+
+/// This function records `origin` as the path `file` was moved from in its `user.mmv.origin` extended attribute.
+/// # Arguments
+/// - `file` - the moved file.
+/// - `origin` - the path of the file before it was moved.
+/// # Return value
+/// Returns `io::Result<()>`, where:
+/// - If succesful - the attribute holds the current UTC timestamp and the absolute path of `origin`, separated by a tab.
+///   An earlier origin of the file is replaced.
+/// - In case of an error, the OS error is returned, e.g. `ENOTSUP` if the file system does not support user extended
+///   attributes, or an error of kind `io::ErrorKind::Unsupported` on platforms other than Linux.
+/// # Example
+/// ```rust
+/// use mmv::origin::{read_origin, tag_origin};
+/// use std::fs;
+/// use tempfile::TempDir;
+///
+/// fn main() -> std::io::Result<()> {
+///     let dir = TempDir::new()?;
+///     let file = dir.path().join("report.txt");
+///     fs::write(&file, "report")?;
+///
+///     tag_origin(&file, &dir.path().join("old/report.txt"))?;
+///     let origin = read_origin(&file)?.unwrap();
+///     assert_eq!(origin.path, dir.path().join("old/report.txt"));
+///     Ok(())
+/// }
+/// ```
+pub fn tag_origin(file: &Path, origin: &Path) -> io::Result<()> {
+    let mut value = format!("{}\t", timestamp(SystemTime::now())).into_bytes();
+    value.extend_from_slice(normalize(&absolute(origin)).as_os_str().as_encoded_bytes());
+    sys::set_xattr(file, OsStr::new(ORIGIN_XATTR), &value)
+}
+
+/// This function reads the origin recorded by `tag_origin`.
+/// # Return value
+/// Returns `io::Result<Option<Origin>>`, where:
+/// - If succesful - returns the origin, or `None` if `file` has none or its file system does not support extended attributes.
+/// - In case of an error, an error of kind `io::ErrorKind::InvalidData` occurs if the attribute was not written by
+///   `tag_origin`, or the OS error if it could not be read.
+pub fn read_origin(file: &Path) -> io::Result<Option<Origin>> {
+    let names = match sys::list_xattrs(file) {
+        Ok(names) => names,
+        Err(error) if error.kind() == io::ErrorKind::Unsupported => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    if !names.iter().any(|name| name == ORIGIN_XATTR) {
+        return Ok(None);
+    }
+
+    let value = sys::get_xattr(file, OsStr::new(ORIGIN_XATTR))?;
+    let separator = value.iter().position(|byte| *byte == b'\t');
+    let (timestamp, path) = match separator {
+        Some(separator) if separator + 1 < value.len() => {
+            (&value[..separator], &value[separator + 1..])
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("The origin of {} is damaged.", file.display()),
+            ))
+        }
+    };
+    #[cfg(unix)]
+    let path = PathBuf::from(<OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(path));
+    #[cfg(not(unix))]
+    let path = PathBuf::from(String::from_utf8_lossy(path).to_string());
+    Ok(Some(Origin {
+        path,
+        timestamp: String::from_utf8_lossy(timestamp).to_string(),
+    }))
+}
+
+/// This function removes the origin recorded by `tag_origin`, e.g. after the file was moved back.
+pub fn remove_origin(file: &Path) -> io::Result<()> {
+    sys::remove_xattr(file, OsStr::new(ORIGIN_XATTR))
+}
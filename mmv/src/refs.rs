@@ -0,0 +1,227 @@
+use crate::errors::MassMoveError;
+use crate::fs_utils::temporary_path;
+use crate::links::{absolute, normalize, relative_path};
+use glob::glob;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The new content of a text file whose references to moved files are rewritten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceUpdate {
+    /// The text file.
+    pub file: PathBuf,
+    /// The content of the file before the update.
+    pub original: String,
+    /// The content of the file after the update.
+    pub updated: String,
+    /// The number of rewritten references.
+    pub replacements: usize,
+}
+
+/// This function finds the references to moved files in the text files matching `pattern` and computes their new content.
+/// # Arguments
+/// - `pattern` - a glob pattern of the text files to update, e.g. `docs/**/*.md`.
+/// - `moved` - the moved files as `(source, destination)` pairs.
+/// # Return value
+/// Returns `Result<Vec<ReferenceUpdate>, MassMoveError>`, where:
+/// - If succesful - returns the updates of the files containing at least one reference. No file is written.
+/// - In case of an error, a `MassMoveError::PatternError` occurs if `pattern` is invalid.
+/// # Example
+/// ```rust
+/// use mmv::errors::MassMoveError;
+/// use mmv::refs::find_reference_updates;
+/// use std::fs;
+/// use tempfile::TempDir;
+///
+/// fn main() -> Result<(), MassMoveError> {
+///     let dir = TempDir::new()?;
+///     fs::write(dir.path().join("index.md"), "![logo](img/img_1.png)\n")?;
+///
+///     let moved = vec![(dir.path().join("img/img_1.png"), dir.path().join("img/image-1.png"))];
+///     let updates = find_reference_updates(&format!("{}/*.md", dir.path().display()), &moved)?;
+///     assert_eq!(updates[0].updated, "![logo](img/image-1.png)\n");
+///     Ok(())
+/// }
+/// ```
+/// # Behavior
+/// - A reference is either the path of a moved file relative to the directory of the text file, or the old file name if
+///   it is unique among the moved files. It is only rewritten if it is not part of a longer name, e.g. `img_1.png` is
+///   not rewritten in `big_img_1.png` or `img_1.png.bak`. A file name is not rewritten after a path separator either,
+///   e.g. in `other/img_1.png`, which names another file, but it is in `./img_1.png`.
+/// - All references are rewritten in a single pass, so renames like `a -> b`, `b -> c` do not affect each other.
+/// - Files that are not valid UTF-8 are skipped.
+pub fn find_reference_updates(
+    pattern: &str,
+    moved: &[(PathBuf, PathBuf)],
+) -> Result<Vec<ReferenceUpdate>, MassMoveError> {
+    let mut updates = Vec::new();
+    for file in glob(pattern)?.flatten() {
+        if !file.is_file() {
+            continue;
+        }
+        let Ok(original) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let (updated, replacements) = replace_references(&original, &references(&file, moved))?;
+        if replacements > 0 {
+            updates.push(ReferenceUpdate {
+                file,
+                original,
+                updated,
+                replacements,
+            });
+        }
+    }
+    Ok(updates)
+}
+
+/// This function writes the updated content of a text file, replacing the file atomically and keeping its permissions.
+/// # Errors
+/// - `MassMoveError::FileChangedError` - if the file was changed since the update was computed.
+/// - `MassMoveError::StdIOError` - if the file could not be written.
+pub fn apply_reference_update(update: &ReferenceUpdate) -> Result<(), MassMoveError> {
+    if fs::read_to_string(&update.file)? != update.original {
+        return Err(MassMoveError::FileChangedError {
+            path: update.file.clone(),
+        });
+    }
+
+    let temporary_file = temporary_path(&update.file);
+    let permissions = fs::metadata(&update.file)?.permissions();
+    let written = fs::write(&temporary_file, &update.updated)
+        .and_then(|_| fs::set_permissions(&temporary_file, permissions))
+        .and_then(|_| fs::rename(&temporary_file, &update.file));
+    if let Err(error) = written {
+        let _ = fs::remove_file(&temporary_file);
+        return Err(error.into());
+    }
+    Ok(())
+}
+
+/// Returns the changed lines of the update as a diff, e.g.
+/// ```text
+/// --- docs/index.md
+/// +++ docs/index.md
+/// @@ -3 +3 @@
+/// -![logo](img/img_1.png)
+/// +![logo](img/image-1.png)
+/// ```
+pub fn reference_diff(update: &ReferenceUpdate) -> String {
+    let file = update.file.display();
+    let mut diff = format!("--- {}\n+++ {}\n", file, file);
+    // References never contain line breaks, so the lines of both versions correspond to each other.
+    for (index, (old, new)) in update
+        .original
+        .lines()
+        .zip(update.updated.lines())
+        .enumerate()
+    {
+        if old != new {
+            let _ = write!(diff, "@@ -{0} +{0} @@\n-{1}\n+{2}\n", index + 1, old, new);
+        }
+    }
+    diff
+}
+
+/// Returns the old references to the moved files as seen from `file`, mapped to the new ones.
+fn references(file: &Path, moved: &[(PathBuf, PathBuf)]) -> HashMap<String, String> {
+    let directory = normalize(&absolute(file))
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let mut references = HashMap::new();
+    // `None` marks a file name shared by moved files with different new names.
+    let mut names: HashMap<String, Option<String>> = HashMap::new();
+
+    for (source, destination) in moved {
+        let source = normalize(&absolute(source));
+        let destination = normalize(&absolute(destination));
+        let old = path_string(&relative_path(&directory, &source));
+        let new = path_string(&relative_path(&directory, &destination));
+        if old != new {
+            references.insert(old, new);
+        }
+
+        if let (Some(old_name), Some(new_name)) = (source.file_name(), destination.file_name()) {
+            let new_name = new_name.to_string_lossy().to_string();
+            names
+                .entry(old_name.to_string_lossy().to_string())
+                .and_modify(|name| {
+                    if name.as_ref() != Some(&new_name) {
+                        *name = None;
+                    }
+                })
+                .or_insert(Some(new_name));
+        }
+    }
+
+    for (old_name, new_name) in names {
+        if let Some(new_name) = new_name.filter(|new_name| *new_name != old_name) {
+            references.entry(old_name).or_insert(new_name);
+        }
+    }
+    references
+}
+
+/// Replaces every reference in `text` that is not part of a longer name. Returns the new text and the number of replacements.
+fn replace_references(
+    text: &str,
+    references: &HashMap<String, String>,
+) -> Result<(String, usize), MassMoveError> {
+    if references.is_empty() {
+        return Ok((text.to_string(), 0));
+    }
+    // The longest reference wins if several start at the same position.
+    let mut old: Vec<&String> = references.keys().collect();
+    old.sort_by(|left, right| right.len().cmp(&left.len()).then(left.cmp(right)));
+    let alternatives: Vec<String> = old.iter().map(|old| regex::escape(old)).collect();
+    let regex = Regex::new(&alternatives.join("|"))?;
+
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let is_separator = |c: char| matches!(c, '/' | '\\');
+    let mut updated = String::with_capacity(text.len());
+    let mut replacements = 0;
+    let mut copied = 0;
+    let mut start = 0;
+    while let Some(found) = regex.find_at(text, start) {
+        let before = text[..found.start()].chars().next_back();
+        // A bare name after a separator is the last component of another path, unless it is `./name`.
+        let in_directory = |text: &str| {
+            text.strip_suffix('.')
+                .is_some_and(|text| !text.ends_with(is_name_char))
+        };
+        let other_path = !found.as_str().contains(is_separator)
+            && before.is_some_and(is_separator)
+            && !in_directory(&text[..found.start() - 1]);
+        let mut after = text[found.end()..].chars();
+        // A dot ends a sentence unless the name continues after it.
+        let continues = match after.next() {
+            Some('.') => after.next().is_some_and(is_name_char),
+            after => after.is_some_and(is_name_char),
+        };
+        if before.is_some_and(is_name_char) || other_path || continues {
+            start = found.start()
+                + text[found.start()..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8);
+            continue;
+        }
+        updated.push_str(&text[copied..found.start()]);
+        updated.push_str(&references[found.as_str()]);
+        replacements += 1;
+        copied = found.end();
+        start = found.end();
+    }
+    updated.push_str(&text[copied..]);
+    Ok((updated, replacements))
+}
+
+fn path_string(path: &Path) -> String {
+    path.display().to_string().replace('\\', "/")
+}
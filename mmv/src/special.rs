@@ -0,0 +1,117 @@
+use crate::copy::preserve_metadata;
+use crate::sys;
+use clap::ValueEnum;
+use std::{
+    fs::{self, Metadata},
+    io,
+    path::Path,
+};
+
+/// What happens to source files that are named pipes, sockets or device nodes.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpecialPolicy {
+    /// Leave special files in place.
+    #[default]
+    Skip,
+    /// Move or copy special files by creating the same kind of node at the destination.
+    Recreate,
+}
+
+/// Returns what kind of special file `metadata` describes, e.g. `named pipe`, or `None` for regular files, directories
+/// and symbolic links.
+pub fn special_kind(metadata: &Metadata) -> Option<&'static str> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        let file_type = metadata.file_type();
+        if file_type.is_fifo() {
+            Some("named pipe")
+        } else if file_type.is_socket() {
+            Some("socket")
+        } else if file_type.is_char_device() {
+            Some("character device")
+        } else if file_type.is_block_device() {
+            Some("block device")
+        } else {
+            None
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Returns the metadata of `path`, or of the file it points to if `follow` is set and `path` is a symbolic link.
+pub fn file_metadata(path: &Path, follow: bool) -> io::Result<Metadata> {
+    if follow {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    }
+}
+
+/// Returns whether `path` is a named pipe, a socket or a device node. If `follow` is set, a symbolic link is checked
+/// by the file it points to, since reading through the link would block on a named pipe just like reading the pipe.
+pub fn is_special(path: &Path, follow: bool) -> bool {
+    file_metadata(path, follow).is_ok_and(|metadata| special_kind(&metadata).is_some())
+}
+
+/// This function creates `destination` as a node of the same kind and device number as the special file `source`,
+/// with `mkfifo` for named pipes and `mknod` otherwise.
+/// # Arguments
+/// - `source` - the special file.
+/// - `metadata` - the metadata of `source`, or of the file it points to if a symbolic link is followed.
+/// - `destination` - the path of the new node, which must not exist.
+/// # Return value
+/// Returns `io::Result<Vec<String>>`, where:
+/// - If succesful - the node exists with the ownership, permissions and timestamps of `source`, as far as the process is
+///   allowed to set them. Returns the names of the attributes that could not be set (see `copy::preserve_metadata`).
+/// - In case of an error, the OS error is returned, e.g. `EPERM` if the process is not allowed to create device nodes.
+/// # Example
+/// ```rust
+/// use mmv::special::{recreate, special_kind};
+/// use std::{fs, process::Command};
+/// use tempfile::TempDir;
+///
+/// fn main() -> std::io::Result<()> {
+///     let dir = TempDir::new()?;
+///     let source = dir.path().join("queue");
+///     Command::new("mkfifo").arg(&source).status()?;
+///
+///     let destination = dir.path().join("queue_copy");
+///     let unpreserved = recreate(&source, &fs::symlink_metadata(&source)?, &destination)?;
+///     assert!(unpreserved.is_empty());
+///     assert_eq!(special_kind(&fs::symlink_metadata(&destination)?), Some("named pipe"));
+///     Ok(())
+/// }
+/// ```
+/// # Behavior
+/// The content of a special file is never read, so a named pipe without a writer cannot block the batch.
+pub fn recreate(source: &Path, metadata: &Metadata, destination: &Path) -> io::Result<Vec<String>> {
+    let Some(kind) = special_kind(metadata) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a special file.", source.display()),
+        ));
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let permissions = metadata.mode() & 0o7777;
+        if kind == "named pipe" {
+            sys::mkfifo(destination, permissions)?;
+        } else {
+            sys::mknod(destination, metadata.mode(), metadata.rdev())?;
+        }
+        Ok(preserve_metadata(metadata, source, destination))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (kind, destination);
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
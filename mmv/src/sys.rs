@@ -0,0 +1,547 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs, io,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// This function installs a `SIGINT` handler that only records the interruption, see `interrupted`.
+/// # Behavior
+/// - The first Ctrl-C lets the batch finish or roll back the current file. The handler then restores the default action,
+///   so a second Ctrl-C terminates the process immediately.
+/// - System calls are not restarted after the signal, so a blocking read, e.g. at a prompt, fails with `EINTR`.
+#[cfg(unix)]
+pub fn catch_interrupts() {
+    extern "C" fn on_interrupt(_signal: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    // SAFETY: the handler only touches an atomic, and `action` is fully initialized before it is installed.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_interrupt as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESETHAND;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+    }
+}
+
+/// This function installs a Ctrl-C handler. Interruptions are only caught on Unix.
+#[cfg(not(unix))]
+pub fn catch_interrupts() {}
+
+/// Returns whether the process received `SIGINT` since `catch_interrupts` was called.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// This function creates `destination` as a copy-on-write clone of `source` with the `FICLONE` ioctl.
+/// # Return value
+/// Returns `io::Result<()>`, where:
+/// - If succesful - `destination` shares its extents with `source` and has the same permissions.
+/// - In case of an error, `destination` is removed and the OS error is returned, e.g. `EOPNOTSUPP` or `EXDEV`
+///   if the file system does not support reflinks or the files are on different file systems.
+pub fn reflink(source: &Path, destination: &Path) -> io::Result<()> {
+    let source_file = fs::File::open(source)?;
+    let destination_file = fs::File::create(destination)?;
+    if let Err(error) = clone_file(&source_file, &destination_file) {
+        drop(destination_file);
+        let _ = fs::remove_file(destination);
+        return Err(error);
+    }
+    destination_file.set_permissions(source_file.metadata()?.permissions())?;
+    Ok(())
+}
+
+/// This function replaces the content of the open file `destination` with a copy-on-write clone of `source`
+/// with the `FICLONE` ioctl.
+#[cfg(target_os = "linux")]
+pub fn clone_file(source: &fs::File, destination: &fs::File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: both descriptors are open for the duration of the call.
+    let result = unsafe {
+        libc::ioctl(
+            destination.as_raw_fd(),
+            libc::FICLONE as _,
+            source.as_raw_fd(),
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// This function clones the content of a file. Reflinks are only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn clone_file(_source: &fs::File, _destination: &fs::File) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Reflinks are only supported on Linux.",
+    ))
+}
+
+/// This function copies up to `length` bytes at `offset` of `source` to the same offset of `destination` inside
+/// the kernel with `copy_file_range`.
+/// # Return value
+/// Returns `io::Result<usize>`, where:
+/// - If succesful - returns the number of copied bytes, `0` at the end of `source`.
+/// - In case of an error, the OS error is returned, e.g. `EXDEV` if the kernel cannot copy between the file systems,
+///   or an error of kind `io::ErrorKind::Unsupported` on platforms other than Linux.
+#[cfg(target_os = "linux")]
+pub fn copy_file_range(
+    source: &fs::File,
+    destination: &fs::File,
+    offset: u64,
+    length: usize,
+) -> io::Result<usize> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut source_offset = offset as libc::loff_t;
+    let mut destination_offset = offset as libc::loff_t;
+    // SAFETY: both descriptors are open and both offsets are valid for the duration of the call.
+    let result = unsafe {
+        libc::copy_file_range(
+            source.as_raw_fd(),
+            &mut source_offset,
+            destination.as_raw_fd(),
+            &mut destination_offset,
+            length,
+            0,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result as usize)
+}
+
+/// This function copies a range of a file inside the kernel. `copy_file_range` is only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn copy_file_range(
+    _source: &fs::File,
+    _destination: &fs::File,
+    _offset: u64,
+    _length: usize,
+) -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "copy_file_range is only supported on Linux.",
+    ))
+}
+
+/// This function lists the ranges of `file` that hold data, skipping the holes of a sparse file, with
+/// `lseek(SEEK_DATA)` and `lseek(SEEK_HOLE)`.
+/// # Return value
+/// Returns the `(start, end)` offsets of the data ranges up to `length`. If the file system cannot report holes,
+/// the whole file is a single range.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+pub fn data_ranges(file: &fs::File, length: u64) -> io::Result<Vec<(u64, u64)>> {
+    use std::os::unix::io::AsRawFd;
+
+    let seek = |offset: u64, whence: libc::c_int| {
+        // SAFETY: the descriptor is open for the duration of the call.
+        let result = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+        if result == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result as u64)
+        }
+    };
+
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    while offset < length {
+        let start = match seek(offset, libc::SEEK_DATA) {
+            Ok(start) => start,
+            // No more data after `offset`.
+            Err(error) if error.raw_os_error() == Some(libc::ENXIO) => break,
+            Err(error) if ranges.is_empty() && error.raw_os_error() == Some(libc::EINVAL) => {
+                return Ok(vec![(0, length)])
+            }
+            Err(error) => return Err(error),
+        };
+        let end = seek(start, libc::SEEK_HOLE)?.min(length);
+        if start >= end {
+            break;
+        }
+        ranges.push((start, end));
+        offset = end;
+    }
+    Ok(ranges)
+}
+
+/// This function lists the ranges of `file` that hold data. Holes are only detected on Linux and FreeBSD,
+/// elsewhere the whole file is a single range.
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+pub fn data_ranges(_file: &fs::File, length: u64) -> io::Result<Vec<(u64, u64)>> {
+    Ok(vec![(0, length)])
+}
+
+/// This function evicts the cached pages of `file` with `posix_fadvise(POSIX_FADV_DONTNEED)`, so it is read again
+/// from the disk. Only pages already written back are evicted, so the file should be flushed with `fsync` first.
+#[cfg(target_os = "linux")]
+pub fn drop_cache(file: &fs::File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: the descriptor is open for the duration of the call.
+    let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    if result != 0 {
+        return Err(io::Error::from_raw_os_error(result));
+    }
+    Ok(())
+}
+
+/// This function evicts the cached pages of a file. It does nothing on platforms other than Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn drop_cache(_file: &fs::File) -> io::Result<()> {
+    Ok(())
+}
+
+/// This function creates a symbolic link at `link` pointing to `target`.
+#[cfg(unix)]
+pub fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// This function creates a symbolic link at `link` pointing to the file `target`.
+#[cfg(windows)]
+pub fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Returns the real user ID of the process.
+#[cfg(unix)]
+pub fn user_id() -> u32 {
+    // SAFETY: `getuid` has no preconditions and cannot fail.
+    unsafe { libc::getuid() }
+}
+
+/// Returns the real user ID of the process. There are no user IDs outside Unix.
+#[cfg(not(unix))]
+pub fn user_id() -> u32 {
+    0
+}
+
+/// This function formats `time` in the local time zone as `YYYY-MM-DDThh:mm:ss` with `localtime_r`.
+#[cfg(unix)]
+pub fn local_time(time: SystemTime) -> io::Result<String> {
+    let seconds = timespec(time).tv_sec;
+    // SAFETY: `tm` is plain data, and `localtime_r` only writes into it.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&seconds, &mut tm) }.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    ))
+}
+
+/// This function formats `time` as `YYYY-MM-DDThh:mm:ss`. The local time zone is only known on Unix.
+#[cfg(not(unix))]
+pub fn local_time(_time: SystemTime) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "The local time zone is only known on Unix.",
+    ))
+}
+
+/// This function sets the access and modification times of `path` without opening it, so it also works for named pipes
+/// and device nodes.
+#[cfg(unix)]
+pub fn set_times(path: &Path, accessed: SystemTime, modified: SystemTime) -> io::Result<()> {
+    let path = c_path(path)?;
+    let times = [timespec(accessed), timespec(modified)];
+    // SAFETY: the path is a valid nul-terminated string and `times` holds two timestamps.
+    if unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), times.as_ptr(), 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// This function sets the access and modification times of `path`.
+#[cfg(not(unix))]
+pub fn set_times(path: &Path, accessed: SystemTime, modified: SystemTime) -> io::Result<()> {
+    let times = fs::FileTimes::new()
+        .set_accessed(accessed)
+        .set_modified(modified);
+    fs::File::open(path)?.set_times(times)
+}
+
+#[cfg(unix)]
+fn timespec(time: SystemTime) -> libc::timespec {
+    let (seconds, nanoseconds) = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, since.subsec_nanos() as i64),
+        Err(error) => {
+            let before = error.duration();
+            match before.subsec_nanos() {
+                0 => (-(before.as_secs() as i64), 0),
+                nanoseconds => (
+                    -(before.as_secs() as i64) - 1,
+                    1_000_000_000 - nanoseconds as i64,
+                ),
+            }
+        }
+    };
+    libc::timespec {
+        tv_sec: seconds as libc::time_t,
+        tv_nsec: nanoseconds as libc::c_long,
+    }
+}
+
+/// This function creates a named pipe at `path` with `mkfifo`.
+#[cfg(unix)]
+pub fn mkfifo(path: &Path, mode: u32) -> io::Result<()> {
+    let path = c_path(path)?;
+    // SAFETY: the path is a valid nul-terminated string for the duration of the call.
+    if unsafe { libc::mkfifo(path.as_ptr(), mode as libc::mode_t) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// This function creates a file system node at `path` with `mknod`. `mode` holds the file type and the permissions,
+/// `device` the device number of character and block devices.
+#[cfg(unix)]
+pub fn mknod(path: &Path, mode: u32, device: u64) -> io::Result<()> {
+    let path = c_path(path)?;
+    // SAFETY: the path is a valid nul-terminated string for the duration of the call.
+    if unsafe { libc::mknod(path.as_ptr(), mode as libc::mode_t, device as libc::dev_t) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// This function renames `source` to `destination` unless `destination` already exists.
+/// # Return value
+/// Returns `io::Result<()>`, where:
+/// - If succesful - `source` was renamed.
+/// - In case of an error, an error of kind `io::ErrorKind::AlreadyExists` occurs if `destination` exists, or any other error of `rename`.
+/// # Behavior
+/// On Linux the check and the rename are a single atomic `renameat2(RENAME_NOREPLACE)` call, so a file created by another
+/// process in the meantime is never overwritten. Where the kernel or the file system does not support `RENAME_NOREPLACE`
+/// (`ENOSYS` or `EINVAL`), and on other platforms, this falls back to a best-effort check of `destination` followed by
+/// a plain rename, which leaves a short window for such a race.
+pub fn rename_noreplace(source: &Path, destination: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let source_path = c_path(source)?;
+        let destination_path = c_path(destination)?;
+        // SAFETY: both paths are valid nul-terminated strings for the duration of the call.
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_renameat2,
+                libc::AT_FDCWD,
+                source_path.as_ptr(),
+                libc::AT_FDCWD,
+                destination_path.as_ptr(),
+                libc::RENAME_NOREPLACE,
+            )
+        };
+        if result == 0 {
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        if !matches!(
+            error.raw_os_error(),
+            Some(libc::ENOSYS) | Some(libc::EINVAL)
+        ) {
+            return Err(error);
+        }
+    }
+
+    if fs::symlink_metadata(destination).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "The destination file already exists.",
+        ));
+    }
+    fs::rename(source, destination)
+}
+
+/// This function atomically swaps `first` and `second` with `renameat2(RENAME_EXCHANGE)`. Both paths must exist.
+/// # Return value
+/// Returns `io::Result<()>`, where:
+/// - If succesful - each path refers to the file the other one referred to before.
+/// - In case of an error, the OS error is returned, e.g. `EINVAL` or `ENOSYS` if the file system or the kernel does not support
+///   `RENAME_EXCHANGE`, or an error of kind `io::ErrorKind::Unsupported` on platforms other than Linux.
+#[cfg(target_os = "linux")]
+pub fn rename_exchange(first: &Path, second: &Path) -> io::Result<()> {
+    let first = c_path(first)?;
+    let second = c_path(second)?;
+    // SAFETY: both paths are valid nul-terminated strings for the duration of the call.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            first.as_ptr(),
+            libc::AT_FDCWD,
+            second.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// This function atomically swaps two files. Atomic exchange is only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn rename_exchange(_first: &Path, _second: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Atomic exchange is only supported on Linux.",
+    ))
+}
+
+/// This function lists the names of the extended attributes of `path`, including POSIX ACLs stored as `system.posix_acl_*`.
+#[cfg(target_os = "linux")]
+pub fn list_xattrs(path: &Path) -> io::Result<Vec<OsString>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = c_path(path)?;
+    loop {
+        // SAFETY: a null buffer of size 0 only queries the required size.
+        let size = unsafe { libc::listxattr(path.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buffer = vec![0u8; size as usize];
+        // SAFETY: the buffer is valid for `buffer.len()` bytes.
+        let size =
+            unsafe { libc::listxattr(path.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len()) };
+        if size < 0 {
+            let error = io::Error::last_os_error();
+            // The list grew between the two calls.
+            if error.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+            return Err(error);
+        }
+        buffer.truncate(size as usize);
+        return Ok(buffer
+            .split(|byte| *byte == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| OsStr::from_bytes(name).to_owned())
+            .collect());
+    }
+}
+
+/// This function reads the value of the extended attribute `name` of `path`.
+#[cfg(target_os = "linux")]
+pub fn get_xattr(path: &Path, name: &OsStr) -> io::Result<Vec<u8>> {
+    let path = c_path(path)?;
+    let name = c_path(Path::new(name))?;
+    loop {
+        // SAFETY: a null buffer of size 0 only queries the required size.
+        let size = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buffer = vec![0u8; size as usize];
+        // SAFETY: the buffer is valid for `buffer.len()` bytes.
+        let size = unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+            )
+        };
+        if size < 0 {
+            let error = io::Error::last_os_error();
+            // The value grew between the two calls.
+            if error.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+            return Err(error);
+        }
+        buffer.truncate(size as usize);
+        return Ok(buffer);
+    }
+}
+
+/// This function sets the extended attribute `name` of `path` to `value`, creating or replacing it.
+#[cfg(target_os = "linux")]
+pub fn set_xattr(path: &Path, name: &OsStr, value: &[u8]) -> io::Result<()> {
+    let path = c_path(path)?;
+    let name = c_path(Path::new(name))?;
+    // SAFETY: all pointers are valid for the duration of the call.
+    let result = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// This function removes the extended attribute `name` of `path`.
+#[cfg(target_os = "linux")]
+pub fn remove_xattr(path: &Path, name: &OsStr) -> io::Result<()> {
+    let path = c_path(path)?;
+    let name = c_path(Path::new(name))?;
+    // SAFETY: both pointers are valid for the duration of the call.
+    if unsafe { libc::removexattr(path.as_ptr(), name.as_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// This function lists the names of the extended attributes of `path`. Extended attributes are only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn list_xattrs(_path: &Path) -> io::Result<Vec<OsString>> {
+    Ok(Vec::new())
+}
+
+/// This function reads the value of an extended attribute. Extended attributes are only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn get_xattr(_path: &Path, _name: &OsStr) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Extended attributes are only supported on Linux.",
+    ))
+}
+
+/// This function sets an extended attribute. Extended attributes are only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn set_xattr(_path: &Path, _name: &OsStr, _value: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Extended attributes are only supported on Linux.",
+    ))
+}
+
+/// This function removes an extended attribute. Extended attributes are only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn remove_xattr(_path: &Path, _name: &OsStr) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Extended attributes are only supported on Linux.",
+    ))
+}
+
+#[cfg(unix)]
+fn c_path(path: &Path) -> io::Result<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "The path contains a nul byte."))
+}
@@ -0,0 +1,228 @@
+use crate::errors::MassMoveError;
+use crate::links::{absolute, normalize};
+use crate::sys;
+use std::{
+    env,
+    ffi::OsString,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// This function moves `path` into the trash of the current user, following the freedesktop.org Trash specification,
+/// so it can be restored by desktop file managers.
+/// # Arguments
+/// - `path` - the file to trash.
+/// # Return value
+/// Returns `Result<PathBuf, MassMoveError>`, where:
+/// - If succesful - returns the path of the file inside the `files` directory of the trash.
+/// - In case of an error, a `MassMoveError::StdIOError` occurs if no trash directory could be used or the file could not
+///   be moved into it. The file is then left in place.
+/// # Behavior
+/// - The file is trashed into `$XDG_DATA_HOME/Trash` (`~/.local/share/Trash` by default) if it is on the same file system.
+///   Otherwise it is trashed into `$topdir/.Trash/$UID` if the mount point `$topdir` has a `.Trash` directory with the
+///   sticky bit set, or else into `$topdir/.Trash-$UID`, which is created if needed. The file is never copied.
+/// - The name is reserved by creating `info/NAME.trashinfo` exclusively, with the original path and the deletion date.
+///   If the name is taken, `NAME.2`, `NAME.3`, ... are tried. The original path is absolute in the home trash and relative
+///   to `$topdir` in a per-mount trash.
+pub fn trash_file(path: &Path) -> Result<PathBuf, MassMoveError> {
+    trash_file_in(home_trash().as_deref(), path)
+}
+
+/// This function moves `path` into a trash like `trash_file`, but with `home_trash` as the home trash directory
+/// instead of the one given by the environment, see `home_trash`.
+/// # Example
+/// ```rust
+/// use mmv::errors::MassMoveError;
+/// use mmv::trash::trash_file_in;
+/// use std::fs;
+/// use tempfile::TempDir;
+///
+/// fn main() -> Result<(), MassMoveError> {
+///     let dir = TempDir::new()?;
+///     let home_trash = dir.path().join("data/Trash");
+///     let file = dir.path().join("report.txt");
+///     fs::write(&file, "report")?;
+///
+///     let trashed = trash_file_in(Some(&home_trash), &file)?;
+///     assert_eq!(trashed, home_trash.join("files/report.txt"));
+///     assert!(!file.exists());
+///     assert!(home_trash.join("info/report.txt.trashinfo").exists());
+///     Ok(())
+/// }
+/// ```
+pub fn trash_file_in(home_trash: Option<&Path>, path: &Path) -> Result<PathBuf, MassMoveError> {
+    let path = normalize(&absolute(path));
+    let device = device(&fs::symlink_metadata(&path)?);
+    let (trash_dir, top_dir) = trash_dir(home_trash, &path, device)?;
+    let files_dir = trash_dir.join("files");
+    let info_dir = trash_dir.join("info");
+    create_private_dir(&files_dir)?;
+    create_private_dir(&info_dir)?;
+
+    let original_path = match &top_dir {
+        Some(top_dir) => path.strip_prefix(top_dir).unwrap_or(&path),
+        None => &path,
+    };
+    let info = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(original_path),
+        sys::local_time(SystemTime::now())?
+    );
+
+    let name = path.file_name().unwrap_or_default();
+    for n in 1.. {
+        let mut trashed_name = name.to_owned();
+        if n > 1 {
+            trashed_name.push(format!(".{}", n));
+        }
+        let trashed_file = files_dir.join(&trashed_name);
+        let mut info_name = trashed_name;
+        info_name.push(".trashinfo");
+        let info_file = info_dir.join(info_name);
+
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_file)
+        {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error.into()),
+        };
+        // A file left in the trash without its info file, e.g. by an interrupted run, is never replaced.
+        let moved = if fs::symlink_metadata(&trashed_file).is_ok() {
+            Err(io::Error::from(io::ErrorKind::AlreadyExists))
+        } else {
+            file.write_all(info.as_bytes())
+                .and_then(|_| file.sync_all())
+                .and_then(|_| sys::rename_noreplace(&path, &trashed_file))
+        };
+        match moved {
+            Ok(()) => return Ok(trashed_file),
+            Err(error) => {
+                let _ = fs::remove_file(&info_file);
+                if error.kind() != io::ErrorKind::AlreadyExists {
+                    return Err(error.into());
+                }
+            }
+        }
+    }
+    unreachable!()
+}
+
+/// Returns the home trash directory, `$XDG_DATA_HOME/Trash` or `~/.local/share/Trash`.
+pub fn home_trash() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(data_home.join("Trash"))
+}
+
+/// Returns the trash directory for a file on `device`, together with the mount point if it is a per-mount trash.
+fn trash_dir(
+    home_trash: Option<&Path>,
+    path: &Path,
+    device: u64,
+) -> io::Result<(PathBuf, Option<PathBuf>)> {
+    if let Some(home_trash) = home_trash {
+        // The trash may not exist yet, so the file system of its nearest existing ancestor counts.
+        let home_device = home_trash
+            .ancestors()
+            .find_map(|ancestor| fs::metadata(ancestor).ok())
+            .map(|metadata| self::device(&metadata));
+        if home_device == Some(device) {
+            return Ok((home_trash.to_path_buf(), None));
+        }
+    }
+
+    let top_dir = top_dir(path, device);
+    let uid = sys::user_id().to_string();
+    let shared_trash = top_dir.join(".Trash");
+    if fs::symlink_metadata(&shared_trash)
+        .is_ok_and(|metadata| metadata.is_dir() && is_sticky(&metadata))
+    {
+        let user_trash = shared_trash.join(&uid);
+        if create_private_dir(&user_trash).is_ok() {
+            return Ok((user_trash, Some(top_dir)));
+        }
+    }
+
+    let mut name = OsString::from(".Trash-");
+    name.push(&uid);
+    let user_trash = top_dir.join(name);
+    create_private_dir(&user_trash)?;
+    Ok((user_trash, Some(top_dir)))
+}
+
+/// Returns the mount point of the file system of `path`: its highest ancestor on the same device.
+fn top_dir(path: &Path, device: u64) -> PathBuf {
+    path.ancestors()
+        .skip(1)
+        .take_while(|ancestor| {
+            fs::metadata(ancestor).is_ok_and(|metadata| self::device(&metadata) == device)
+        })
+        .last()
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+/// Creates `path` with permissions `0700` unless it exists. An existing path must be a directory and not a symbolic link.
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("The trash directory {} is not a directory.", path.display()),
+        )),
+        Err(_) => {
+            let mut builder = fs::DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+            builder.create(path)
+        }
+    }
+}
+
+fn is_sticky(metadata: &fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()) & 0o1000 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        false
+    }
+}
+
+fn device(metadata: &fs::Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::MetadataExt::dev(metadata)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        0
+    }
+}
+
+/// Escapes `path` as the `Path` key of a `.trashinfo` file: every byte except unreserved characters and `/` is
+/// written as `%XX`.
+fn percent_encode(path: &Path) -> String {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .map(|&byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' => (byte as char).to_string(),
+            b'-' | b'_' | b'.' | b'!' | b'~' | b'*' | b'\'' | b'(' | b')' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -2,12 +2,29 @@
 mod tests {
     use mmv::{
         args::CLI,
+        conflict::{backup_dir_path, ConflictPolicy, ConflictPrompt},
+        copy::{copy_file, CopyOptions, CopyStrategy, Progress},
         errors::MassMoveError,
-        fs_utils::{capture_files_by_pattern, rename_file_by_pattern},
-        mmv::mmv,
+        fs_utils::{
+            capture_files_by_pattern, is_mmv_file, move_file, rename_file_by_pattern,
+            temporary_path, MoveOptions, MoveOutcome, Operation,
+        },
+        interactive::{Decision, Interactive, InteractiveMode},
+        journal::{Journal, JOURNAL_PREFIX},
+        links::SymlinkPolicy,
+        lock::Lock,
+        mmv::{mmv, restore},
+        open_files::OpenFilePolicy,
+        origin::read_origin,
+        special::SpecialPolicy,
+        sys,
+    };
+    use std::fs::{self, File, FileTimes};
+    use std::time::{Duration, SystemTime};
+    use std::{
+        collections::HashSet,
+        io::{self, Cursor, Write},
     };
-    use std::fs::File;
-    use std::{collections::HashSet, io::Write};
     use tempfile::TempDir;
 
     #[test]
@@ -158,6 +175,7 @@ mod tests {
             source_pattern: format!("{}/some_*_filename.txt", dir.path().display()),
             destination_pattern: format!("{}/changed_#1_filename.txt", dir.path().display()),
             force: false,
+            ..Default::default()
         };
 
         let result = mmv(args);
@@ -165,4 +183,1591 @@ mod tests {
         assert!(!source_file.exists());
         assert!(destination_file.exists());
     }
+
+    #[test]
+    fn test_mmv_keep_going_collects_failures() {
+        let dir = TempDir::new().unwrap();
+        let first_file = dir.path().join("some_a_filename.txt");
+        let second_file = dir.path().join("some_b_filename.txt");
+        let existing_file = dir.path().join("changed_a_filename.txt");
+        File::create(&first_file).unwrap();
+        File::create(&second_file).unwrap();
+        File::create(&existing_file).unwrap();
+
+        let args = CLI {
+            source_pattern: format!("{}/some_*_filename.txt", dir.path().display()),
+            destination_pattern: format!("{}/changed_#1_filename.txt", dir.path().display()),
+            keep_going: true,
+            ..Default::default()
+        };
+
+        let result = mmv(args);
+        if let Err(MassMoveError::BatchError(failures)) = &result {
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].0, first_file.display().to_string());
+            assert!(matches!(
+                &failures[0].1,
+                MassMoveError::FileExistsError(message)
+                    if message.contains(&existing_file.display().to_string())
+            ));
+        } else {
+            panic!("Expected a BatchError.");
+        }
+        assert_eq!(result.unwrap_err().exit_code(), 3);
+        assert!(first_file.exists());
+        assert!(!second_file.exists());
+        assert!(dir.path().join("changed_b_filename.txt").exists());
+    }
+
+    #[test]
+    fn test_interactive_overwrite_asks_only_for_existing_files() {
+        let dir = TempDir::new().unwrap();
+        let existing_file = dir.path().join("existing.txt").display().to_string();
+        let new_file = dir.path().join("new.txt").display().to_string();
+        File::create(&existing_file).unwrap();
+
+        let mut output = Vec::new();
+        let mut interactive = Interactive::new(
+            InteractiveMode::Overwrite,
+            Cursor::new("x\nn\na\n"),
+            &mut output,
+        );
+
+        assert_eq!(
+            interactive.confirm("source.txt", &new_file).unwrap(),
+            Decision::Move {
+                destination: new_file.clone(),
+                overwrite: false
+            }
+        );
+        assert_eq!(
+            interactive.confirm("source.txt", &existing_file).unwrap(),
+            Decision::Skip
+        );
+        assert_eq!(
+            interactive.confirm("source.txt", &existing_file).unwrap(),
+            Decision::Move {
+                destination: existing_file.clone(),
+                overwrite: true
+            }
+        );
+        assert_eq!(
+            interactive.confirm("source.txt", &existing_file).unwrap(),
+            Decision::Move {
+                destination: existing_file.clone(),
+                overwrite: true
+            }
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("(overwrite)?").count(), 3);
+    }
+
+    #[test]
+    fn test_interactive_always_all_does_not_confirm_overwrites() {
+        let dir = TempDir::new().unwrap();
+        let existing_file = dir.path().join("existing.txt").display().to_string();
+        let new_file = dir.path().join("new.txt").display().to_string();
+        File::create(&existing_file).unwrap();
+
+        let mut output = Vec::new();
+        let mut interactive =
+            Interactive::new(InteractiveMode::Always, Cursor::new("a\nn\n"), &mut output);
+
+        let moved = Decision::Move {
+            destination: new_file.clone(),
+            overwrite: false,
+        };
+        assert_eq!(interactive.confirm("a.txt", &new_file).unwrap(), moved);
+        assert_eq!(interactive.confirm("b.txt", &new_file).unwrap(), moved);
+        assert_eq!(
+            interactive.confirm("c.txt", &existing_file).unwrap(),
+            Decision::Skip
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("? [y]es").count(), 2);
+        assert_eq!(output.matches("(overwrite)?").count(), 1);
+    }
+
+    #[test]
+    fn test_interactive_edited_name_stays_in_destination_directory() {
+        let dir = TempDir::new().unwrap();
+        let destination = dir.path().join("out/report.txt").display().to_string();
+        let elsewhere = dir.path().join("report.txt").display().to_string();
+        fs::create_dir_all(dir.path().join("out")).unwrap();
+        File::create(dir.path().join("out/report_v2.txt")).unwrap();
+
+        let mut output = Vec::new();
+        let mut interactive = Interactive::new(
+            InteractiveMode::Always,
+            Cursor::new(format!("e\nreport_v2.txt\ny\ne\n{}\ny\n", elsewhere)),
+            &mut output,
+        );
+
+        assert_eq!(
+            interactive.confirm("report.txt", &destination).unwrap(),
+            Decision::Move {
+                destination: dir.path().join("out/report_v2.txt").display().to_string(),
+                overwrite: true
+            }
+        );
+        assert_eq!(
+            interactive.confirm("report.txt", &destination).unwrap(),
+            Decision::Move {
+                destination: elsewhere,
+                overwrite: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_interactive_quits_on_end_of_input() {
+        let mut interactive =
+            Interactive::new(InteractiveMode::Always, Cursor::new("q\n"), Vec::new());
+        assert_eq!(
+            interactive.confirm("a.txt", "b.txt").unwrap(),
+            Decision::Quit
+        );
+
+        let mut interactive =
+            Interactive::new(InteractiveMode::Always, Cursor::new(""), Vec::new());
+        assert_eq!(
+            interactive.confirm("a.txt", "b.txt").unwrap(),
+            Decision::Quit
+        );
+
+        // A read interrupted by Ctrl-C is not retried.
+        struct Interrupted;
+        impl io::Read for Interrupted {
+            fn read(&mut self, _buffer: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::Interrupted.into())
+            }
+        }
+        let interrupted = io::BufReader::new(Interrupted);
+        let mut interactive = Interactive::new(InteractiveMode::Always, interrupted, Vec::new());
+        assert_eq!(
+            interactive.confirm("a.txt", "b.txt").unwrap(),
+            Decision::Quit
+        );
+    }
+
+    #[test]
+    fn test_move_file_ask_shares_prompt_across_conflicts() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let source = |n: u32| dir.path().join(format!("a_{}.txt", n));
+        let destination = |n: u32| dir.path().join(format!("b_{}.txt", n));
+        for n in 1..=3 {
+            fs::write(source(n), "new")?;
+            fs::write(destination(n), "old")?;
+        }
+
+        let options = MoveOptions {
+            policy: ConflictPolicy::Ask,
+            prompt: Some(ConflictPrompt::new(Cursor::new("n\na\n"), io::sink())),
+            ..Default::default()
+        };
+        let outcomes: Vec<MoveOutcome> = (1..=3)
+            .map(|n| move_file(&source(n), &destination(n), &options))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(outcomes[0], MoveOutcome::Skipped);
+        for n in 2..=3 {
+            assert!(matches!(outcomes[n - 1], MoveOutcome::Moved { .. }));
+            assert_eq!(fs::read_to_string(destination(n as u32))?, "new");
+        }
+
+        fs::write(source(2), "new")?;
+        fs::write(source(3), "new")?;
+        let options = MoveOptions {
+            policy: ConflictPolicy::Ask,
+            prompt: Some(ConflictPrompt::new(Cursor::new("q\ny\n"), io::sink())),
+            ..Default::default()
+        };
+        assert_eq!(
+            move_file(&source(2), &destination(2), &options)?,
+            MoveOutcome::Quit
+        );
+        assert!(source(2).exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_file_conflict_policies() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("source.txt");
+        let destination_file = dir.path().join("report.txt");
+        fs::write(&destination_file, "old")?;
+        let options = |policy| MoveOptions {
+            policy,
+            ..Default::default()
+        };
+
+        fs::write(&source_file, "skipped")?;
+        let outcome = move_file(
+            &source_file,
+            &destination_file,
+            &options(ConflictPolicy::Skip),
+        )?;
+        assert_eq!(outcome, MoveOutcome::Skipped);
+        assert!(source_file.exists());
+
+        let outcome = move_file(
+            &source_file,
+            &destination_file,
+            &options(ConflictPolicy::Rename),
+        )?;
+        let renamed_file = dir.path().join("report_1.txt");
+        assert_eq!(
+            outcome,
+            MoveOutcome::Moved {
+                destination: renamed_file.clone(),
+                unpreserved: vec![],
+                strategy: None
+            }
+        );
+        assert_eq!(fs::read_to_string(&renamed_file)?, "skipped");
+
+        for content in ["first", "second", "third"] {
+            fs::write(&source_file, content)?;
+            move_file(
+                &source_file,
+                &destination_file,
+                &options(ConflictPolicy::Backup),
+            )?;
+        }
+        assert_eq!(fs::read_to_string(&destination_file)?, "third");
+        assert_eq!(fs::read_to_string(dir.path().join("report.txt~"))?, "old");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("report.txt.~1~"))?,
+            "first"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("report.txt.~2~"))?,
+            "second"
+        );
+
+        fs::write(&source_file, "tiny")?;
+        let outcome = move_file(
+            &source_file,
+            &destination_file,
+            &options(ConflictPolicy::Larger),
+        )?;
+        assert_eq!(outcome, MoveOutcome::Skipped);
+        fs::write(&source_file, "much larger content")?;
+        move_file(
+            &source_file,
+            &destination_file,
+            &options(ConflictPolicy::Larger),
+        )?;
+        assert_eq!(
+            fs::read_to_string(&destination_file)?,
+            "much larger content"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_force_with_backup_dir() {
+        let dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("some_part_filename.txt");
+        let destination_file = dir.path().join("changed_part_filename.txt");
+        fs::write(&source_file, "new").unwrap();
+        fs::write(&destination_file, "old").unwrap();
+
+        let args = CLI {
+            source_pattern: format!("{}/some_*_filename.txt", dir.path().display()),
+            destination_pattern: format!("{}/changed_#1_filename.txt", dir.path().display()),
+            force: true,
+            backup_dir: Some(backup_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        assert!(mmv(args).is_ok());
+        assert_eq!(fs::read_to_string(&destination_file).unwrap(), "new");
+
+        let relative_dir: std::path::PathBuf = dir.path().components().skip(1).collect();
+        let backups: Vec<_> = fs::read_dir(backup_dir.path().join(relative_dir))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(backups.len(), 1);
+        let backup_name = backups[0]
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        assert!(backup_name.starts_with("changed_part_filename.txt.2"));
+        assert!(backup_name.ends_with('Z'));
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), "old");
+    }
+
+    #[test]
+    fn test_backup_dir_path_resolves_parent_directories() -> Result<(), MassMoveError> {
+        let backup_dir = std::path::Path::new("backups");
+        let inside = backup_dir_path(backup_dir, std::path::Path::new("a/report.txt"));
+        let outside = backup_dir_path(backup_dir, std::path::Path::new("../a/report.txt"));
+
+        let parent: std::path::PathBuf = std::env::current_dir()?
+            .parent()
+            .unwrap()
+            .components()
+            .skip(1)
+            .collect();
+        assert_eq!(inside.parent(), Some(backup_dir.join("a").as_path()));
+        assert_eq!(
+            outside.parent(),
+            Some(backup_dir.join(parent).join("a").as_path())
+        );
+        let resolved = backup_dir_path(backup_dir, std::path::Path::new("a/./b/../report.txt"));
+        assert_eq!(resolved.parent(), Some(backup_dir.join("a").as_path()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_file_operations_keep_source() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("artifact.bin");
+        fs::write(&source_file, "artifact")?;
+
+        for (operation, name) in [
+            (Operation::Copy, "copy.bin"),
+            (Operation::Hardlink, "hardlink.bin"),
+            (Operation::Symlink, "symlink.bin"),
+        ] {
+            let destination_file = dir.path().join(name);
+            fs::write(&destination_file, "old")?;
+            let options = MoveOptions {
+                operation,
+                policy: ConflictPolicy::Overwrite,
+                ..Default::default()
+            };
+            move_file(&source_file, &destination_file, &options)?;
+            assert_eq!(fs::read_to_string(&destination_file)?, "artifact");
+            assert!(!fs::read_dir(dir.path())?.any(|entry| is_mmv_file(&entry.unwrap().path())));
+        }
+
+        assert!(source_file.exists());
+        assert!(fs::symlink_metadata(dir.path().join("symlink.bin"))?
+            .file_type()
+            .is_symlink());
+        fs::write(&source_file, "changed")?;
+        assert_eq!(
+            fs::read_to_string(dir.path().join("hardlink.bin"))?,
+            "changed"
+        );
+        assert_eq!(fs::read_to_string(dir.path().join("copy.bin"))?, "artifact");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_append_merges_parts_in_natural_order() {
+        let dir = TempDir::new().unwrap();
+        for part in [10, 2, 1] {
+            fs::write(
+                dir.path().join(format!("app_web_part{}.log", part)),
+                format!("{}\n", part),
+            )
+            .unwrap();
+        }
+        let destination_file = dir.path().join("app_web.log");
+        fs::write(&destination_file, "0\n").unwrap();
+
+        let args = CLI {
+            source_pattern: format!("{}/app_*_part*.log", dir.path().display()),
+            destination_pattern: format!("{}/app_#1.log", dir.path().display()),
+            append: true,
+            ..Default::default()
+        };
+
+        assert!(mmv(args).is_ok());
+        assert_eq!(
+            fs::read_to_string(&destination_file).unwrap(),
+            "0\n1\n2\n10\n"
+        );
+        for part in [1, 2, 10] {
+            assert!(!dir
+                .path()
+                .join(format!("app_web_part{}.log", part))
+                .exists());
+        }
+    }
+
+    #[test]
+    fn test_mmv_append_skips_destination_among_sources() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let all_file = dir.path().join("all.log");
+        fs::write(&all_file, "0\n")?;
+        for part in [1, 2] {
+            fs::write(
+                dir.path().join(format!("part{}.log", part)),
+                format!("{}\n", part),
+            )?;
+        }
+        let args = CLI {
+            source_pattern: format!("{}/*.log", dir.path().display()),
+            destination_pattern: format!("{}/all.log", dir.path().display()),
+            append: true,
+            ..Default::default()
+        };
+        mmv(args)?;
+        assert_eq!(fs::read_to_string(&all_file)?, "0\n1\n2\n");
+
+        let args = CLI {
+            source_pattern: format!("{}/al*.log", dir.path().display()),
+            destination_pattern: format!("{}/al#1.log", dir.path().display()),
+            append: true,
+            ..Default::default()
+        };
+        mmv(args)?;
+        assert_eq!(fs::read_to_string(&all_file)?, "0\n1\n2\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_preserves_metadata() -> Result<(), MassMoveError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("photo.raw");
+        let destination_file = dir.path().join("copy.raw");
+        fs::write(&source_file, "raw data")?;
+        fs::set_permissions(&source_file, fs::Permissions::from_mode(0o640))?;
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::open(&source_file)?.set_times(
+            FileTimes::new()
+                .set_modified(modified)
+                .set_accessed(modified),
+        )?;
+        let xattr_supported =
+            mmv::sys::set_xattr(&source_file, "user.comment".as_ref(), b"holiday").is_ok();
+
+        let options = CopyOptions {
+            verify: true,
+            ..Default::default()
+        };
+        let report = copy_file(&source_file, &destination_file, &options)?;
+        assert!(report.unpreserved.is_empty(), "{:?}", report.unpreserved);
+
+        let metadata = fs::metadata(&destination_file)?;
+        assert_eq!(metadata.modified()?, modified);
+        assert_eq!(metadata.accessed()?, modified);
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        if xattr_supported {
+            assert_eq!(
+                mmv::sys::get_xattr(&destination_file, "user.comment".as_ref())?,
+                b"holiday"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_file_reports_rename_errors_with_both_paths() {
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("source.txt");
+        let destination_file = dir.path().join("missing_dir/destination.txt");
+
+        let result = move_file(&source_file, &destination_file, &MoveOptions::default());
+        if let Err(MassMoveError::SourceNotFoundError {
+            source,
+            destination,
+        }) = result
+        {
+            assert_eq!(source, source_file);
+            assert_eq!(destination, destination_file);
+        } else {
+            panic!("Expected a SourceNotFoundError.");
+        }
+
+        File::create(&source_file).unwrap();
+        let result = move_file(&source_file, &destination_file, &MoveOptions::default());
+        assert!(matches!(
+            result,
+            Err(MassMoveError::MissingDirectoryError { .. })
+        ));
+        assert!(source_file.exists());
+    }
+
+    #[test]
+    fn test_rename_noreplace_keeps_existing_destination() {
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("source.txt");
+        let destination_file = dir.path().join("destination.txt");
+        fs::write(&source_file, "source").unwrap();
+        fs::write(&destination_file, "destination").unwrap();
+
+        let error = mmv::sys::rename_noreplace(&source_file, &destination_file).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&source_file).unwrap(), "source");
+        assert_eq!(
+            fs::read_to_string(&destination_file).unwrap(),
+            "destination"
+        );
+
+        let result = copy_file(&source_file, &destination_file, &CopyOptions::default());
+        assert!(matches!(result, Err(MassMoveError::FileExistsError(_))));
+        assert_eq!(
+            fs::read_to_string(&destination_file).unwrap(),
+            "destination"
+        );
+    }
+
+    #[test]
+    fn test_move_file_exchange_swaps_existing_destination() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let new_config = dir.path().join("config.new");
+        let config = dir.path().join("config");
+        fs::write(&new_config, "green")?;
+        fs::write(&config, "blue")?;
+
+        let options = MoveOptions {
+            operation: Operation::Exchange,
+            ..Default::default()
+        };
+        match move_file(&new_config, &config, &options) {
+            Ok(outcome) => {
+                assert_eq!(
+                    outcome,
+                    MoveOutcome::Exchanged {
+                        destination: config.clone()
+                    }
+                );
+                assert_eq!(fs::read_to_string(&config)?, "green");
+                assert_eq!(fs::read_to_string(&new_config)?, "blue");
+            }
+            Err(MassMoveError::ExchangeUnsupportedError { .. }) => {
+                assert_eq!(fs::read_to_string(&config)?, "blue");
+            }
+            Err(error) => return Err(error),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_reports_progress() -> Result<(), MassMoveError> {
+        use std::sync::{Arc, Mutex};
+
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("movie.mkv");
+        let destination_file = dir.path().join("copy.mkv");
+        let content = vec![7u8; (5 << 20) / 2];
+        fs::write(&source_file, &content)?;
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let progress = {
+            let reports = Arc::clone(&reports);
+            Progress::new(move |_, copied, total| reports.lock().unwrap().push((copied, total)))
+        };
+        let options = CopyOptions {
+            progress: Some(progress),
+            ..Default::default()
+        };
+        copy_file(&source_file, &destination_file, &options)?;
+
+        let total = content.len() as u64;
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![(1 << 20, total), (2 << 20, total), (total, total)]
+        );
+        assert_eq!(fs::read(&destination_file)?, content);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_verify_detects_corrupted_copy() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("backup.tar");
+        let destination_file = dir.path().join("copy.tar");
+        fs::write(&source_file, vec![7u8; 3 << 20])?;
+
+        // The copy is damaged after the last chunk is written, e.g. by failing media.
+        let damaged = destination_file.clone();
+        let progress = Progress::new(move |_, copied, total| {
+            if copied == total {
+                File::options()
+                    .write(true)
+                    .open(&damaged)
+                    .and_then(|mut file| file.write_all(b"damaged"))
+                    .unwrap()
+            }
+        });
+        let options = CopyOptions {
+            progress: Some(progress),
+            verify: true,
+            strategy: CopyStrategy::ReadWrite,
+            ..Default::default()
+        };
+        assert!(matches!(
+            copy_file(&source_file, &destination_file, &options),
+            Err(MassMoveError::VerificationError { .. })
+        ));
+        assert_eq!(fs::read(&source_file)?, vec![7u8; 3 << 20]);
+        assert!(fs::read(&destination_file)?.starts_with(b"damaged"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_and_mmv_stop_on_ctrl_c() -> Result<(), MassMoveError> {
+        // The interruption is global to the process, so it is tested in a child running only this test.
+        if std::env::var_os("MMV_TEST_INTERRUPT").is_none() {
+            let output = std::process::Command::new(std::env::current_exe()?)
+                .args(["--exact", "tests::test_copy_file_and_mmv_stop_on_ctrl_c"])
+                .env("MMV_TEST_INTERRUPT", "1")
+                .output()?;
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stdout)
+            );
+            return Ok(());
+        }
+
+        let dir = TempDir::new().unwrap();
+        sys::catch_interrupts();
+
+        // The batch runs first, as the interruption cannot be undone.
+        if let Some(shm) = tmpfs_dir() {
+            fs::write(dir.path().join("movie.bak"), vec![7u8; 128 << 20])?;
+            fs::write(dir.path().join("notes.bak"), "notes")?;
+            fs::write(dir.path().join("song.bak"), "song")?;
+
+            // Ctrl-C is pressed while the first file is being copied to the other file system.
+            let shm_path = shm.path().to_path_buf();
+            let watcher = std::thread::spawn(move || loop {
+                let copying = fs::read_dir(&shm_path).unwrap().any(|entry| {
+                    entry
+                        .unwrap()
+                        .file_name()
+                        .to_string_lossy()
+                        .ends_with(".mmv-tmp")
+                });
+                if copying {
+                    // SAFETY: the handler installed by `catch_interrupts` only sets a flag.
+                    unsafe { libc::raise(libc::SIGINT) };
+                    return;
+                }
+            });
+            let args = CLI {
+                source_pattern: format!("{}/*.bak", dir.path().display()),
+                destination_pattern: format!("{}/#1.bak", shm.path().display()),
+                ..Default::default()
+            };
+            let error = mmv(args).unwrap_err();
+            watcher.join().unwrap();
+            assert!(matches!(error, MassMoveError::InterruptedError(3)));
+            assert_eq!(error.exit_code(), 130);
+            // Only the journal of the batch is left, which names the removed partial copy.
+            let left: Vec<_> = fs::read_dir(shm.path())?
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            assert!(matches!(&left[..], [name] if name.starts_with(JOURNAL_PREFIX)));
+            assert_eq!(fs::metadata(dir.path().join("movie.bak"))?.len(), 128 << 20);
+            assert_eq!(fs::read_to_string(dir.path().join("notes.bak"))?, "notes");
+            assert_eq!(fs::read_to_string(dir.path().join("song.bak"))?, "song");
+        } else {
+            // SAFETY: the handler installed by `catch_interrupts` only sets a flag.
+            unsafe { libc::raise(libc::SIGINT) };
+        }
+        assert!(sys::interrupted());
+
+        // An interrupted copy is rolled back.
+        let source_file = dir.path().join("movie.mkv");
+        let destination_file = dir.path().join("copy.mkv");
+        fs::write(&source_file, vec![7u8; 3 << 20])?;
+        let options = CopyOptions {
+            strategy: CopyStrategy::ReadWrite,
+            ..Default::default()
+        };
+        assert!(matches!(
+            copy_file(&source_file, &destination_file, &options),
+            Err(MassMoveError::StdIOError(error)) if error.kind() == io::ErrorKind::Interrupted
+        ));
+        assert!(source_file.exists());
+        assert!(!destination_file.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_fails_if_source_shrinks() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("growing.log");
+        let destination_file = dir.path().join("copy.log");
+
+        for strategy in [CopyStrategy::CopyFileRange, CopyStrategy::ReadWrite] {
+            fs::write(&source_file, vec![7u8; 3 << 20])?;
+            // Another process truncates the source after the first chunk was copied.
+            let truncated = source_file.clone();
+            let progress = Progress::new(move |_, _, _| {
+                File::options()
+                    .write(true)
+                    .open(&truncated)
+                    .and_then(|file| file.set_len(1 << 20))
+                    .unwrap()
+            });
+            let options = CopyOptions {
+                progress: Some(progress),
+                strategy,
+                ..Default::default()
+            };
+            let result = copy_file(&source_file, &destination_file, &options);
+            assert!(
+                matches!(&result, Err(MassMoveError::StdIOError(error)) if error.kind() == io::ErrorKind::UnexpectedEof)
+            );
+            assert!(!destination_file.exists());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_resume_finishes_interrupted_batch() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let source_pattern = format!("{}/a_*.txt", dir.path().display());
+        let destination_pattern = format!("{}/b_#1.txt", dir.path().display());
+        let source = |n: u32| dir.path().join(format!("a_{}.txt", n));
+        let destination = |n: u32| dir.path().join(format!("b_{}.txt", n));
+        for n in 1..=3 {
+            fs::write(source(n), format!("part {}", n))?;
+        }
+
+        // The interrupted batch recorded the first file and renamed the copy of the second one into place.
+        let journal_path = Journal::path_for(&source_pattern, &destination_pattern);
+        let mut journal =
+            Journal::open(&journal_path, &source_pattern, &destination_pattern, false)?;
+        journal.start()?;
+        journal.record(
+            &source(1).display().to_string(),
+            &destination(1).display().to_string(),
+        )?;
+        journal.record_copy(
+            &source(2).display().to_string(),
+            &destination(2).display().to_string(),
+        )?;
+        fs::copy(source(2), destination(2))?;
+
+        let args = CLI {
+            source_pattern: source_pattern.clone(),
+            destination_pattern: destination_pattern.clone(),
+            ..Default::default()
+        };
+        assert!(matches!(mmv(args), Err(MassMoveError::JournalError(_))));
+        assert!(source(3).exists());
+
+        let args = CLI {
+            source_pattern,
+            destination_pattern,
+            resume: true,
+            ..Default::default()
+        };
+        mmv(args)?;
+
+        assert!(source(1).exists());
+        assert!(!destination(1).exists());
+        assert!(!source(2).exists());
+        assert_eq!(fs::read_to_string(destination(2))?, "part 2");
+        assert!(!source(3).exists());
+        assert_eq!(fs::read_to_string(destination(3))?, "part 3");
+        assert!(!journal_path.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_resume_keeps_sources_without_recorded_copy() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("a_1.txt");
+        let destination_file = dir.path().join("b_1.txt");
+        fs::write(&source_file, "AAAA")?;
+        fs::write(&destination_file, "BBBB")?;
+        let modified = fs::metadata(&source_file)?.modified()?;
+        File::options()
+            .write(true)
+            .open(&destination_file)?
+            .set_times(FileTimes::new().set_modified(modified))?;
+        let source_pattern = format!("{}/a_*.txt", dir.path().display());
+        let destination_pattern = format!("{}/b_#1.txt", dir.path().display());
+        let args = CLI {
+            source_pattern: source_pattern.clone(),
+            destination_pattern: destination_pattern.clone(),
+            resume: true,
+            ..Default::default()
+        };
+        assert!(matches!(mmv(args), Err(MassMoveError::FileExistsError(_))));
+        assert_eq!(fs::read_to_string(&source_file)?, "AAAA");
+        // A plain rename batch writes no journal, also when it fails.
+        assert_eq!(fs::read_dir(dir.path())?.count(), 2);
+
+        // A recorded copy whose content differs is not finished either.
+        let journal_path = Journal::path_for(&source_pattern, &destination_pattern);
+        Journal::open(&journal_path, &source_pattern, &destination_pattern, false)?.record_copy(
+            &source_file.display().to_string(),
+            &destination_file.display().to_string(),
+        )?;
+        let args = CLI {
+            source_pattern,
+            destination_pattern,
+            resume: true,
+            ..Default::default()
+        };
+        assert!(matches!(mmv(args), Err(MassMoveError::FileExistsError(_))));
+        assert_eq!(fs::read_to_string(&source_file)?, "AAAA");
+        assert!(journal_path.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_resume_removes_recorded_temporary_file() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        let source_file = dir.path().join("a_1.txt");
+        let destination_file = dir.path().join("b_1.txt");
+        fs::write(&source_file, "AAAA")?;
+        let source_pattern = format!("{}/a_*.txt", dir.path().display());
+        let destination_pattern = format!("{}/b_#1.txt", dir.path().display());
+
+        // The batch was interrupted while copying, and another run is copying to the same destination.
+        let partial_copy = temporary_path(&destination_file);
+        let other_copy = temporary_path(&destination_file);
+        assert_ne!(partial_copy, other_copy);
+        fs::write(&partial_copy, "AA")?;
+        fs::write(&other_copy, "CC")?;
+        let journal_path = Journal::path_for(&source_pattern, &destination_pattern);
+        Journal::open(&journal_path, &source_pattern, &destination_pattern, false)?
+            .record_temporary(
+                &source_file.display().to_string(),
+                &partial_copy.display().to_string(),
+            )?;
+
+        let args = CLI {
+            source_pattern,
+            destination_pattern,
+            resume: true,
+            ..Default::default()
+        };
+        mmv(args)?;
+        assert_eq!(fs::read_to_string(&destination_file)?, "AAAA");
+        assert!(!partial_copy.exists());
+        assert_eq!(fs::read_to_string(&other_copy)?, "CC");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_jobs_keep_dependent_files_in_order() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        for part in 1..=12 {
+            fs::write(
+                dir.path().join(format!("log_x_{}.txt", part)),
+                format!("x{} ", part),
+            )?;
+            fs::write(
+                dir.path().join(format!("log_y_{}.txt", part)),
+                format!("y{} ", part),
+            )?;
+            fs::write(dir.path().join(format!("photo_{}.jpg", part)), "photo")?;
+        }
+
+        let args = CLI {
+            source_pattern: format!("{}/log_*_*.txt", dir.path().display()),
+            destination_pattern: format!("{}/all_#1.txt", dir.path().display()),
+            append: true,
+            jobs: 4,
+            ..Default::default()
+        };
+        mmv(args)?;
+        let args = CLI {
+            source_pattern: format!("{}/photo_*.jpg", dir.path().display()),
+            destination_pattern: format!("{}/image_#1.jpg", dir.path().display()),
+            jobs: 4,
+            ..Default::default()
+        };
+        mmv(args)?;
+
+        let expected = |name: &str| {
+            (1..=12)
+                .map(|part| format!("{}{} ", name, part))
+                .collect::<String>()
+        };
+        assert_eq!(
+            fs::read_to_string(dir.path().join("all_x.txt"))?,
+            expected("x")
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("all_y.txt"))?,
+            expected("y")
+        );
+        for part in 1..=12 {
+            assert!(dir.path().join(format!("image_{}.jpg", part)).exists());
+            assert!(!dir.path().join(format!("photo_{}.jpg", part)).exists());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_jobs_report_first_failure_in_order() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        for part in 1..=40 {
+            fs::write(dir.path().join(format!("photo_{}.jpg", part)), "photo")?;
+        }
+        // The first file fails because its link is dangling, later files because their destinations exist.
+        fs::remove_file(dir.path().join("photo_3.jpg"))?;
+        std::os::unix::fs::symlink("missing.jpg", dir.path().join("photo_3.jpg"))?;
+        for part in 4..=8 {
+            fs::write(dir.path().join(format!("image_{}.jpg", part)), "image")?;
+        }
+
+        let args = CLI {
+            source_pattern: format!("{}/photo_*.jpg", dir.path().display()),
+            destination_pattern: format!("{}/image_#1.jpg", dir.path().display()),
+            symlinks: SymlinkPolicy::Follow,
+            jobs: 8,
+            ..Default::default()
+        };
+        match mmv(args) {
+            Err(MassMoveError::StdIOError(error)) => {
+                assert_eq!(error.kind(), io::ErrorKind::NotFound)
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(dir.path().join("photo_4.jpg").exists());
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mmv_jobs_print_warnings_and_progress_in_order() -> Result<(), MassMoveError> {
+        let run = |jobs: usize| -> Result<std::process::Output, MassMoveError> {
+            let dir = TempDir::new()?;
+            let destination_dir = tmpfs_dir().unwrap();
+            // The destination is reached through a link, so both runs print the same relative paths.
+            std::os::unix::fs::symlink(destination_dir.path(), dir.path().join("out"))?;
+            for part in 1..=40 {
+                let file = dir.path().join(format!("a_{}", part));
+                if part % 3 == 0 {
+                    mmv::sys::mkfifo(&file, 0o644)?;
+                } else {
+                    fs::write(&file, vec![part as u8; part << 14])?;
+                }
+            }
+            Ok(std::process::Command::new(env!("CARGO_BIN_EXE_mmv"))
+                .args([
+                    "--source-pattern",
+                    "a_*",
+                    "--destination-pattern",
+                    "out/b_#1",
+                ])
+                .args(["--progress", "--jobs", &jobs.to_string()])
+                .current_dir(dir.path())
+                .output()?)
+        };
+        if tmpfs_dir().is_none() {
+            return Ok(());
+        }
+
+        let sequential = run(1)?;
+        let parallel = run(8)?;
+        assert!(sequential.status.success());
+        assert!(String::from_utf8_lossy(&sequential.stderr).contains("Warning: skipped a_3"));
+        assert_eq!(
+            String::from_utf8_lossy(&parallel.stdout),
+            String::from_utf8_lossy(&sequential.stdout)
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&parallel.stderr),
+            String::from_utf8_lossy(&sequential.stderr)
+        );
+
+        Ok(())
+    }
+
+    /// Creates a temporary directory on tmpfs, which supports holes but no reflinks. Tests that need it are skipped,
+    /// with a message, if /dev/shm is not available.
+    fn tmpfs_dir() -> Option<TempDir> {
+        let dir = TempDir::new_in("/dev/shm");
+        if let Err(error) = &dir {
+            eprintln!(
+                "Skipping the tmpfs part of this test: /dev/shm is not available: {}",
+                error
+            );
+        }
+        dir.ok()
+    }
+
+    /// Writes a 16 MiB file with data only in its first and last but one MiB.
+    fn write_sparse_file(path: &std::path::Path) -> Result<Vec<u8>, MassMoveError> {
+        use std::io::{Seek, SeekFrom};
+
+        let mut file = File::create(path)?;
+        file.write_all(&[1u8; 1 << 20])?;
+        file.seek(SeekFrom::Start(14 << 20))?;
+        file.write_all(&[2u8; 1 << 20])?;
+        file.set_len(16 << 20)?;
+        Ok(fs::read(path)?)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn allocated_bytes(path: &std::path::Path) -> Result<u64, MassMoveError> {
+        use std::os::unix::fs::MetadataExt;
+
+        Ok(fs::metadata(path)?.blocks() * 512)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_copy_file_falls_back_from_reflink() -> Result<(), MassMoveError> {
+        let Some(dir) = tmpfs_dir() else {
+            return Ok(());
+        };
+        let source_file = dir.path().join("disk.img");
+        let destination_file = dir.path().join("copy.img");
+        fs::write(&source_file, "content")?;
+
+        let report = copy_file(&source_file, &destination_file, &CopyOptions::default())?;
+
+        assert_eq!(report.strategy, CopyStrategy::CopyFileRange);
+        assert_eq!(fs::read_to_string(&destination_file)?, "content");
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_copy_file_falls_back_from_copy_file_range() -> Result<(), MassMoveError> {
+        let Some(source_dir) = tmpfs_dir() else {
+            return Ok(());
+        };
+        let destination_dir = TempDir::new()?;
+        let source_file = source_dir.path().join("disk.img");
+        let destination_file = destination_dir.path().join("copy.img");
+        let content = write_sparse_file(&source_file)?;
+
+        // Since Linux 5.19 `copy_file_range` fails with `EXDEV` between file systems of different kinds, e.g. from
+        // tmpfs to ext4, which forces the fallback to read/write.
+        let probe = sys::copy_file_range(
+            &File::open(&source_file)?,
+            &File::create(&destination_file)?,
+            0,
+            1,
+        );
+        fs::remove_file(&destination_file)?;
+        if !matches!(&probe, Err(error) if error.kind() == io::ErrorKind::CrossesDevices) {
+            eprintln!(
+                "Skipping this test: copy_file_range from tmpfs to {} returned {:?}",
+                destination_dir.path().display(),
+                probe
+            );
+            return Ok(());
+        }
+
+        let options = CopyOptions {
+            strategy: CopyStrategy::CopyFileRange,
+            ..Default::default()
+        };
+        let report = copy_file(&source_file, &destination_file, &options)?;
+
+        assert_eq!(report.strategy, CopyStrategy::ReadWrite);
+        assert_eq!(fs::read(&destination_file)?, content);
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_copy_file_copy_file_range_keeps_holes() -> Result<(), MassMoveError> {
+        let Some(dir) = tmpfs_dir() else {
+            return Ok(());
+        };
+        let source_file = dir.path().join("disk.img");
+        let destination_file = dir.path().join("copy.img");
+        let content = write_sparse_file(&source_file)?;
+
+        let options = CopyOptions {
+            strategy: CopyStrategy::CopyFileRange,
+            ..Default::default()
+        };
+        let report = copy_file(&source_file, &destination_file, &options)?;
+
+        assert_eq!(report.strategy, CopyStrategy::CopyFileRange);
+        assert_eq!(fs::read(&destination_file)?, content);
+        assert!(allocated_bytes(&destination_file)? <= allocated_bytes(&source_file)?);
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_copy_file_read_write_keeps_holes() -> Result<(), MassMoveError> {
+        let Some(dir) = tmpfs_dir() else {
+            return Ok(());
+        };
+        let source_file = dir.path().join("disk.img");
+        let destination_file = dir.path().join("copy.img");
+        let content = write_sparse_file(&source_file)?;
+
+        let options = CopyOptions {
+            strategy: CopyStrategy::ReadWrite,
+            ..Default::default()
+        };
+        let report = copy_file(&source_file, &destination_file, &options)?;
+
+        assert_eq!(report.strategy, CopyStrategy::ReadWrite);
+        assert_eq!(fs::read(&destination_file)?, content);
+        assert!(allocated_bytes(&destination_file)? <= allocated_bytes(&source_file)?);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_move_file_symlink_policies() -> Result<(), MassMoveError> {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("data"))?;
+        fs::create_dir_all(dir.path().join("links"))?;
+        fs::create_dir_all(dir.path().join("archive/2024"))?;
+        fs::write(dir.path().join("data/report.txt"), "report")?;
+        let link = dir.path().join("links/report.txt");
+        let moved_link = dir.path().join("archive/2024/report.txt");
+
+        symlink("../data/report.txt", &link)?;
+        let options = |symlinks, rewrite_links| MoveOptions {
+            symlinks,
+            rewrite_links,
+            ..Default::default()
+        };
+        assert_eq!(
+            move_file(&link, &moved_link, &options(SymlinkPolicy::Skip, false))?,
+            MoveOutcome::Skipped
+        );
+        assert!(fs::symlink_metadata(&link)?.file_type().is_symlink());
+
+        move_file(&link, &moved_link, &options(SymlinkPolicy::Preserve, true))?;
+        assert_eq!(
+            fs::read_link(&moved_link)?,
+            std::path::PathBuf::from("../../data/report.txt")
+        );
+        assert_eq!(fs::read_to_string(&moved_link)?, "report");
+        assert!(fs::symlink_metadata(&link).is_err());
+
+        move_file(&moved_link, &link, &options(SymlinkPolicy::Follow, false))?;
+        assert!(fs::symlink_metadata(&link)?.is_file());
+        assert_eq!(fs::read_to_string(&link)?, "report");
+        assert!(fs::symlink_metadata(&moved_link).is_err());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("data/report.txt"))?,
+            "report"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mmv_fix_links_keeps_link_style() -> Result<(), MassMoveError> {
+        use std::os::unix::fs::symlink;
+        use std::path::PathBuf;
+
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("files"))?;
+        fs::create_dir_all(dir.path().join("renamed"))?;
+        fs::create_dir_all(dir.path().join("docs/current"))?;
+        fs::write(dir.path().join("files/a_1.txt"), "first")?;
+        fs::write(dir.path().join("files/other.txt"), "other")?;
+        let relative_link = dir.path().join("docs/current/latest.txt");
+        let absolute_link = dir.path().join("docs/absolute.txt");
+        let other_link = dir.path().join("docs/other.txt");
+        symlink("../../files/a_1.txt", &relative_link)?;
+        symlink(dir.path().join("files/a_1.txt"), &absolute_link)?;
+        symlink("../files/other.txt", &other_link)?;
+
+        let args = CLI {
+            source_pattern: format!("{}/files/a_*.txt", dir.path().display()),
+            destination_pattern: format!("{}/renamed/b_#1.txt", dir.path().display()),
+            fix_links: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        mmv(args)?;
+
+        assert_eq!(
+            fs::read_link(&relative_link)?,
+            PathBuf::from("../../renamed/b_1.txt")
+        );
+        assert_eq!(
+            fs::read_link(&absolute_link)?,
+            dir.path().join("renamed/b_1.txt")
+        );
+        assert_eq!(fs::read_to_string(&relative_link)?, "first");
+        assert_eq!(fs::read_to_string(&absolute_link)?, "first");
+        assert_eq!(
+            fs::read_link(&other_link)?,
+            PathBuf::from("../files/other.txt")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_update_refs_rewrites_paths_and_names() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("img"))?;
+        fs::create_dir_all(dir.path().join("css"))?;
+        fs::write(dir.path().join("img/img_1.png"), "one")?;
+        fs::write(dir.path().join("img/img_2.png"), "two")?;
+        let markdown = dir.path().join("index.md");
+        let css = dir.path().join("css/site.css");
+        fs::write(
+            &markdown,
+            "![one](img/img_1.png)\nSee img_2.png. Not big_img_1.png or img_1.png.bak.\n",
+        )?;
+        fs::write(&css, "body { background: url(../img/img_2.png); }\n")?;
+
+        let args = |dry_run| CLI {
+            source_pattern: format!("{}/img/img_*.png", dir.path().display()),
+            destination_pattern: format!("{}/img/image-#1.png", dir.path().display()),
+            update_refs: Some(format!("{}/**/*.[mc]*", dir.path().display())),
+            dry_run,
+            ..Default::default()
+        };
+        mmv(args(true))?;
+        assert!(dir.path().join("img/img_1.png").exists());
+        assert!(fs::read_to_string(&markdown)?.starts_with("![one](img/img_1.png)"));
+
+        mmv(args(false))?;
+        assert!(dir.path().join("img/image-1.png").exists());
+        assert_eq!(
+            fs::read_to_string(&markdown)?,
+            "![one](img/image-1.png)\nSee image-2.png. Not big_img_1.png or img_1.png.bak.\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&css)?,
+            "body { background: url(../img/image-2.png); }\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_update_refs_keeps_other_paths_with_same_name() -> Result<(), MassMoveError> {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("img"))?;
+        fs::create_dir_all(dir.path().join("other/dir"))?;
+        fs::write(dir.path().join("img/logo.png"), "moved")?;
+        fs::write(dir.path().join("other/dir/logo.png"), "kept")?;
+        let markdown = dir.path().join("index.md");
+        fs::write(
+            &markdown,
+            "![a](img/logo.png) ![b](./img/logo.png) ![c](other/dir/logo.png) logo.png\n",
+        )?;
+
+        let args = CLI {
+            source_pattern: format!("{}/img/logo.*", dir.path().display()),
+            destination_pattern: format!("{}/img/brand.#1", dir.path().display()),
+            update_refs: Some(format!("{}/*.md", dir.path().display())),
+            ..Default::default()
+        };
+        mmv(args)?;
+        assert_eq!(
+            fs::read_to_string(&markdown)?,
+            "![a](img/brand.png) ![b](./img/brand.png) ![c](other/dir/logo.png) brand.png\n"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mmv_keeps_hard_links_across_devices() -> Result<(), MassMoveError> {
+        use std::os::unix::fs::MetadataExt;
+
+        let source_dir = TempDir::new()?;
+        let Some(destination_dir) = tmpfs_dir() else {
+            return Ok(());
+        };
+        if fs::metadata(source_dir.path())?.dev() == fs::metadata(destination_dir.path())?.dev() {
+            eprintln!("Skipping this test: /dev/shm is on the same file system as the temporary directory");
+            return Ok(());
+        }
+        fs::write(source_dir.path().join("a_1.txt"), "content")?;
+        fs::hard_link(
+            source_dir.path().join("a_1.txt"),
+            source_dir.path().join("a_2.txt"),
+        )?;
+        fs::hard_link(
+            source_dir.path().join("a_1.txt"),
+            source_dir.path().join("a_3.txt"),
+        )?;
+        fs::write(source_dir.path().join("a_4.txt"), "other")?;
+
+        for jobs in [1, 4] {
+            let (from, to) = if jobs == 1 {
+                (&source_dir, &destination_dir)
+            } else {
+                (&destination_dir, &source_dir)
+            };
+            mmv(CLI {
+                source_pattern: format!("{}/a_*.txt", from.path().display()),
+                destination_pattern: format!("{}/a_#1.txt", to.path().display()),
+                jobs,
+                ..Default::default()
+            })?;
+
+            let inodes: Vec<(u64, u64)> = (1..=3)
+                .map(|index| fs::metadata(to.path().join(format!("a_{}.txt", index))))
+                .map(|metadata| metadata.map(|metadata| (metadata.ino(), metadata.nlink())))
+                .collect::<Result<_, _>>()?;
+            assert!(inodes.iter().all(|inode| *inode == inodes[0]));
+            assert_eq!(inodes[0].1, 3);
+            assert_eq!(fs::metadata(to.path().join("a_4.txt"))?.nlink(), 1);
+            assert_eq!(fs::read_to_string(to.path().join("a_2.txt"))?, "content");
+            assert_eq!(fs::read_dir(from.path())?.count(), 0);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mmv_special_files_are_skipped_or_recreated() -> Result<(), MassMoveError> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        let source_dir = TempDir::new()?;
+        let Some(destination_dir) = tmpfs_dir() else {
+            return Ok(());
+        };
+        let fifo = source_dir.path().join("a_queue");
+        mmv::sys::mkfifo(&fifo, 0o640)?;
+        fs::write(source_dir.path().join("a_notes.txt"), "notes")?;
+        let args = |special| CLI {
+            source_pattern: format!("{}/a_*", source_dir.path().display()),
+            destination_pattern: format!("{}/b_#1", destination_dir.path().display()),
+            special,
+            ..Default::default()
+        };
+
+        mmv(args(SpecialPolicy::Skip))?;
+        assert!(fs::symlink_metadata(&fifo)?.file_type().is_fifo());
+        assert!(!destination_dir.path().join("b_queue").exists());
+        assert_eq!(
+            fs::read_to_string(destination_dir.path().join("b_notes.txt"))?,
+            "notes"
+        );
+
+        mmv(args(SpecialPolicy::Recreate))?;
+        let metadata = fs::symlink_metadata(destination_dir.path().join("b_queue"))?;
+        assert!(metadata.file_type().is_fifo());
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert!(fs::symlink_metadata(&fifo).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mmv_followed_links_to_special_files_are_never_read() -> Result<(), MassMoveError> {
+        use std::os::unix::fs::FileTypeExt;
+
+        let dir = TempDir::new()?;
+        let fifo = dir.path().join("queue");
+        mmv::sys::mkfifo(&fifo, 0o640)?;
+        let link = dir.path().join("a_queue");
+        std::os::unix::fs::symlink(&fifo, &link)?;
+        let args = |special| CLI {
+            source_pattern: format!("{}/a_*", dir.path().display()),
+            destination_pattern: format!("{}/b_#1", dir.path().display()),
+            symlinks: SymlinkPolicy::Follow,
+            special,
+            ..Default::default()
+        };
+
+        // Copying the pipe the link points to would block without a writer.
+        mmv(args(SpecialPolicy::Skip))?;
+        assert!(fs::symlink_metadata(&link)?.file_type().is_symlink());
+        assert!(!dir.path().join("b_queue").exists());
+
+        mmv(args(SpecialPolicy::Recreate))?;
+        assert!(fs::symlink_metadata(dir.path().join("b_queue"))?
+            .file_type()
+            .is_fifo());
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert!(fs::symlink_metadata(&fifo)?.file_type().is_fifo());
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mmv_force_with_trash() -> Result<(), MassMoveError> {
+        let dir = TempDir::new()?;
+        let data_home = dir.path().join("data");
+        // The trash is found through the environment, which is only changed for a child process.
+        for n in 1..=2 {
+            fs::write(dir.path().join("new report.txt"), format!("new {}", n))?;
+            fs::write(dir.path().join("old report.txt"), format!("old {}", n))?;
+            let output = std::process::Command::new(env!("CARGO_BIN_EXE_mmv"))
+                .arg("--source-pattern")
+                .arg(format!("{}/new *.txt", dir.path().display()))
+                .arg("--destination-pattern")
+                .arg(format!("{}/old #1.txt", dir.path().display()))
+                .args(["--force", "--trash"])
+                .env("XDG_DATA_HOME", &data_home)
+                .output()?;
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            assert_eq!(
+                fs::read_to_string(dir.path().join("old report.txt"))?,
+                format!("new {}", n)
+            );
+        }
+
+        let trash = data_home.join("Trash");
+        assert_eq!(
+            fs::read_to_string(trash.join("files/old report.txt"))?,
+            "old 1"
+        );
+        assert_eq!(
+            fs::read_to_string(trash.join("files/old report.txt.2"))?,
+            "old 2"
+        );
+        let info = fs::read_to_string(trash.join("info/old report.txt.2.trashinfo"))?;
+        let mut lines = info.lines();
+        assert_eq!(lines.next(), Some("[Trash Info]"));
+        assert_eq!(
+            lines.next(),
+            Some(format!("Path={}/old%20report.txt", dir.path().display()).as_str())
+        );
+        assert!(lines
+            .next()
+            .is_some_and(|line| line.starts_with("DeletionDate=")));
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mmv_tag_origin_and_restore() -> Result<(), MassMoveError> {
+        let dir = TempDir::new()?;
+        fs::create_dir(dir.path().join("archive"))?;
+        for n in 1..=2 {
+            fs::write(dir.path().join(format!("a_{}.txt", n)), n.to_string())?;
+        }
+        mmv(CLI {
+            source_pattern: format!("{}/a_*.txt", dir.path().display()),
+            destination_pattern: format!("{}/archive/b_#1.txt", dir.path().display()),
+            tag_origin: true,
+            ..Default::default()
+        })?;
+        let origin = read_origin(&dir.path().join("archive/b_2.txt"))?.unwrap();
+        assert_eq!(origin.path, dir.path().join("a_2.txt"));
+
+        // The original path is taken again, so the file is only restored with `force`.
+        fs::write(dir.path().join("a_1.txt"), "new")?;
+        fs::write(dir.path().join("archive/untagged.txt"), "untagged")?;
+        let pattern = format!("{}/archive/*.txt", dir.path().display());
+        assert!(matches!(
+            restore(&pattern, false),
+            Err(MassMoveError::FileExistsError(_))
+        ));
+        restore(&pattern, true)?;
+
+        for n in 1..=2 {
+            let file = dir.path().join(format!("a_{}.txt", n));
+            assert_eq!(fs::read_to_string(&file)?, n.to_string());
+            assert_eq!(read_origin(&file)?, None);
+        }
+        assert!(dir.path().join("archive/untagged.txt").exists());
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mmv_skip_open_files() -> Result<(), MassMoveError> {
+        let dir = TempDir::new()?;
+        fs::write(dir.path().join("app.log"), "log")?;
+        fs::write(dir.path().join("old.log"), "old")?;
+        let mut daemon = std::process::Command::new("sleep")
+            .arg("30")
+            .stdin(File::open(dir.path().join("app.log"))?)
+            .spawn()?;
+        let args = |skip_open| CLI {
+            source_pattern: format!("{}/*.log", dir.path().display()),
+            destination_pattern: format!("{}/#1.log.1", dir.path().display()),
+            skip_open: Some(skip_open),
+            ..Default::default()
+        };
+
+        let failed = mmv(args(OpenFilePolicy::Fail));
+        let skipped = mmv(args(OpenFilePolicy::Skip));
+        daemon.kill()?;
+        daemon.wait()?;
+
+        match failed {
+            Err(MassMoveError::FileInUseError { path, processes }) => {
+                assert_eq!(path, dir.path().join("app.log"));
+                // The name of the process may not be updated yet right after it was spawned.
+                assert_eq!(processes.len(), 1);
+                assert!(processes[0].starts_with(&format!("{} (", daemon.id())));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        skipped?;
+        assert!(dir.path().join("app.log").exists());
+        assert!(!dir.path().join("app.log.1").exists());
+        assert_eq!(fs::read_to_string(dir.path().join("old.log.1"))?, "old");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmv_lock_fails_fast_or_waits() -> Result<(), MassMoveError> {
+        let dir = TempDir::new()?;
+        fs::create_dir_all(dir.path().join("inbox/new"))?;
+        fs::create_dir_all(dir.path().join("inbox/done"))?;
+        fs::write(dir.path().join("inbox/new/a_1.csv"), "1")?;
+        let args = |wait| CLI {
+            source_pattern: format!("{}/inbox/new/a_*.csv", dir.path().display()),
+            destination_pattern: format!("{}/inbox/done/b_#1.csv", dir.path().display()),
+            lock: Some(None),
+            wait,
+            ..Default::default()
+        };
+        let lock_path = dir.path().join("inbox/.mmv-lock");
+        let lock = Lock::acquire(&lock_path, None)?;
+
+        assert!(matches!(
+            mmv(args(None)),
+            Err(MassMoveError::LockError { path }) if path == lock_path
+        ));
+        assert!(dir.path().join("inbox/new/a_1.csv").exists());
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            drop(lock);
+        });
+        // A wait too long for a deadline waits until the lock is released.
+        mmv(args(Some(u64::MAX)))?;
+        release.join().unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("inbox/done/b_1.csv"))?,
+            "1"
+        );
+        assert!(lock_path.exists());
+
+        Ok(())
+    }
 }